@@ -0,0 +1,48 @@
+//! Shared logic
+
+use tpic6c596::{Connector, Controller};
+
+static USAGE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+pub fn set_usage(value: impl Into<String>) {
+    USAGE.set(value.into()).expect("Not set yet.")
+}
+
+pub fn usage(args: &[String]) -> ! {
+    eprintln!(
+        "Usage: {} <warmup> <benchmark> {}",
+        args[0],
+        USAGE.get().map(|s| s.as_str()).unwrap_or("")
+    );
+    std::process::exit(1);
+}
+
+pub fn arg<T: std::str::FromStr>(args: &[String], index: usize, error: &str) -> T {
+    match args[index].replace('_', "").parse() {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("Error: {error}");
+            usage(args);
+        }
+    }
+}
+
+pub fn opt_arg<T: std::str::FromStr>(args: &[String], index: usize, default: T) -> T {
+    match args.get(index).map(|v| v.parse()) {
+        Some(Ok(n)) => n,
+        None | Some(Err(_)) => default,
+    }
+}
+
+pub fn controller(args: &[String], chain: usize) -> Controller<impl Connector> {
+    let controller = match args.get(4) {
+        Some(description) => Controller::open(description, chain),
+        None => Controller::from_env(chain),
+    }
+    .expect("connector");
+
+    #[cfg(feature = "batched")]
+    let controller = controller.batched();
+
+    controller
+}
@@ -74,6 +74,7 @@ impl Connector for GpioConnector {
             Pin::Control => self.control.is_set_high(),
             Pin::Data => self.data.is_set_high(),
             Pin::Latch => self.latch.is_set_high(),
+            Pin::Clear => true,
         }
     }
 
@@ -83,6 +84,7 @@ impl Connector for GpioConnector {
             Pin::Control => &mut self.control,
             Pin::Data => &mut self.data,
             Pin::Latch => &mut self.latch,
+            Pin::Clear => return,
         };
 
         if state {
@@ -7,9 +7,10 @@ import sys
 if sys.version_info[0] < 3:
     raise Exception("Python 3 is required.")
 
+import os
 import socket as sockets
 import time
-from typing import Final, Literal, Protocol
+from typing import Callable, Final, Literal, Protocol
 from abc import abstractmethod
 
 
@@ -35,6 +36,71 @@ class Pins(Protocol):
         raise NotImplementedError
 
 
+class Tee(Pins):
+    def __init__(self, primary: Pins, *mirrors: Pins):
+        """
+        Mirror every pin change of the primary pins to other pins.
+
+        Args:
+          primary (Pins): The pins whose control state is read.
+          mirrors (Pins): Pins receiving the same changes, errors are ignored.
+        """
+        self.primary = primary
+        self.mirrors = list(mirrors)
+
+    def _forward(self, name: str, on: Literal[0, 1]):
+        getattr(self.primary, name)(on)
+
+        for mirror in self.mirrors:
+            try:
+                getattr(mirror, name)(on)
+            except OSError:
+                pass
+
+    def set_data(self, on: Literal[0, 1]):
+        self._forward("set_data", on)
+
+    def set_clock(self, on: Literal[0, 1]):
+        self._forward("set_clock", on)
+
+    def set_latch(self, on: Literal[0, 1]):
+        self._forward("set_latch", on)
+
+    def set_control(self, on: Literal[0, 1]):
+        self._forward("set_control", on)
+
+    def get_control(self) -> Literal[0, 1]:
+        return self.primary.get_control()
+
+
+def mirrored(pins: Pins) -> Pins:
+    """
+    Mirror pins to the emulator socket in `TPIC6C596_MIRROR`, when set.
+
+    The server sets it for choreographies when started with `--mirror` or `--record`.
+    """
+    socket = os.environ.get("TPIC6C596_MIRROR")
+    if not socket or not hasattr(sockets, "AF_UNIX"):
+        return pins
+
+    return Tee(pins, Emulator(socket=socket))
+
+
+def served(pins: Callable[[], Pins]) -> Pins:
+    """
+    Send pin changes to the server socket in `TPIC6C596_SERVER`, when set.
+
+    The server sets it for choreographies when started with `--watchdog`, it then
+    drives the lights itself and the pins are never opened. Otherwise the pins are
+    opened and mirrored, see `mirrored`.
+    """
+    socket = os.environ.get("TPIC6C596_SERVER")
+    if not socket or not hasattr(sockets, "AF_UNIX"):
+        return mirrored(pins())
+
+    return Emulator(socket=socket)
+
+
 PI_SUPPORTED = False
 try:
     import RPi.GPIO as GPIO
@@ -366,12 +432,14 @@ if PI_SUPPORTED:
           Lights: An instance of the Lights class configured with the specified shift register and number of lights.
         """
         shift_register: TPIC6C596 = TPIC6C596(
-            pins=RPiPins(
-                data_pin=data_pin,
-                latch_pin=latch_pin,
-                clock_pin=clock_pin,
-                control_pin=control_pin,
-                mode=mode,
+            pins=served(
+                lambda: RPiPins(
+                    data_pin=data_pin,
+                    latch_pin=latch_pin,
+                    clock_pin=clock_pin,
+                    control_pin=control_pin,
+                    mode=mode,
+                )
             ),
             chain=chain or lights // 8,
         )
@@ -410,7 +478,7 @@ if hasattr(sockets, "AF_UNIX"):
         chain: int | None = None,
     ) -> Lights:
         shift_register: TPIC6C596 = TPIC6C596(
-            pins=Emulator(socket=socket),
+            pins=served(lambda: Emulator(socket=socket)),
             chain=chain or lights // 8,
         )
 
@@ -47,12 +47,14 @@ impl Case<'_> {
     /// Benchmark arguments.
     #[must_use]
     pub fn arguments(&self) -> Arguments {
-        self.namespace
-            .map_or(self.case.arguments.clone(), |namespace| {
+        self.namespace.map_or_else(
+            || self.case.arguments.clone(),
+            |namespace| {
                 let mut args = namespace.arguments.clone();
                 args.append(self.case.arguments.clone());
                 args
-            })
+            },
+        )
     }
 
     /// Benchmark iterations.
@@ -123,7 +123,7 @@ impl Iterations {
     #[must_use]
     pub fn warmup(&self) -> usize {
         self.warmup
-            .map_or_else(|| self.benchmark().ilog10() as usize * 2, |v| v)
+            .unwrap_or_else(|| self.benchmark().ilog10() as usize * 2)
     }
 
     /// Benchmark iterations.
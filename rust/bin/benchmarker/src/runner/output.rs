@@ -13,6 +13,7 @@ pub struct Output {
     timings: Option<Timings>,
 }
 
+/// Benchmark timings
 #[derive(Debug, Clone, Copy)]
 pub struct Timings {
     /// Total execution time in nanoseconds.
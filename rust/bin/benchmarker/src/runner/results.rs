@@ -1,6 +1,9 @@
 //! Collected results from all benchmarks
 
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Write,
+};
 
 use crate::{benchmark::Case, implementation::Implementation};
 
@@ -73,7 +76,7 @@ impl Results {
 
     /// Turn results into json.
     pub fn to_json(&self) -> String {
-        let mut json = format!(r#"{{"device":{:#?},"benchmarks":{{"#, self.device,);
+        let mut json = format!(r#"{{"device":{:#?},"benchmarks":{{"#, self.device);
         let mut first_case = true;
 
         for (case, details) in &self.benchmarks {
@@ -123,10 +126,11 @@ impl Results {
                 json.push(':');
 
                 json.push('{');
-                json.push_str(&format!(
+                let _ = write!(
+                    json,
                     r#""warmup":{},"benchmark":{},"execution":{}"#,
                     timings.warmup, timings.benchmark, timings.execution
-                ));
+                );
                 json.push('}');
             }
 
@@ -1,15 +1,26 @@
 //! Unix Datagram IPC
 
 use std::{
-    os::unix::net::UnixDatagram,
+    collections::HashMap,
+    os::unix::net::{SocketAddr, UnixDatagram},
     path::{Path, PathBuf},
     sync::atomic::Ordering,
     time::Duration,
 };
 
-use tpic6c596::Pin;
+use tpic6c596::{Datagram, Packet, PROTOCOL_VERSION};
 
-use crate::{Sender, StopSignal};
+use crate::{ClientClock, Sender, StopSignal};
+
+/// State of a bound client, reset by its handshake.
+#[derive(Debug, Default)]
+struct Client {
+    /// Sequence number of the last datagram.
+    sequence: Option<u32>,
+
+    /// Clock of the timed pin changes.
+    clock: ClientClock,
+}
 
 /// IPC through datagrams.
 #[derive(Debug)]
@@ -23,28 +34,80 @@ pub struct Ipc {
 
 impl Ipc {
     /// Listen for datagram messages
+    ///
+    /// Accepts both the legacy single byte format and the framed protocol.
     #[allow(clippy::needless_pass_by_value)]
     pub fn listen(self, stop: StopSignal, sender: &Sender) {
-        let mut buffer = vec![0; 1024];
+        let mut buffer = vec![0; 65536];
+        let mut clients = HashMap::new();
 
         while !stop.load(Ordering::Relaxed) {
-            if let Ok(received) = self.socket.recv(&mut buffer) {
-                for message in buffer.iter().take(received) {
-                    let pin = match message & 0b0000_1111 {
-                        1 => Some(Pin::Data),
-                        2 => Some(Pin::Control),
-                        3 => Some(Pin::Clock),
-                        4 => Some(Pin::Latch),
-                        _ => None,
-                    };
-
-                    if let Some(pin) = pin {
-                        let state = (message & 0b1000_0000) != 0;
-                        let _ = sender.send(crate::Message { pin, state });
+            if let Ok((received, from)) = self.socket.recv_from(&mut buffer) {
+                let at = std::time::Instant::now();
+
+                if let Some(datagram) = Datagram::decode(&buffer[..received]) {
+                    self.handle(&datagram, &from, at, &mut clients, sender);
+                }
+            }
+        }
+    }
+
+    /// Handle a received datagram.
+    fn handle(
+        &self,
+        datagram: &Datagram,
+        from: &SocketAddr,
+        at: std::time::Instant,
+        clients: &mut HashMap<PathBuf, Client>,
+        sender: &Sender,
+    ) {
+        let reply = |packet| {
+            let reply = Datagram {
+                sequence: datagram.sequence,
+                acknowledge: false,
+                packet,
+            };
+            let _ = self.socket.send_to_addr(&reply.encode(), from);
+        };
+
+        if let Packet::Hello(_) = datagram.packet {
+            if let Some(path) = from.as_pathname() {
+                clients.remove(path);
+            }
+            reply(Packet::Hello(PROTOCOL_VERSION));
+            return;
+        }
+
+        // Unbound clients can not handshake, they only send legacy datagrams.
+        let mut unbound = Client::default();
+        let client = from.as_pathname().map_or(&mut unbound, |path| {
+            clients.entry(path.to_path_buf()).or_default()
+        });
+
+        if let Some(sequence) = datagram.sequence {
+            match client.sequence.replace(sequence) {
+                // Resent after a lost acknowledgement.
+                Some(last) if last == sequence => {
+                    if datagram.acknowledge {
+                        reply(Packet::Ack);
+                    }
+                    return;
+                }
+                Some(last) => {
+                    let missing = lost(last, sequence);
+                    if missing > 0 {
+                        let _ = sender.send(crate::Message::Lost(missing));
                     }
                 }
+                None => {}
             }
         }
+
+        crate::forward(&datagram.packet, at, true, &mut client.clock, sender);
+
+        if datagram.acknowledge {
+            reply(Packet::Ack);
+        }
     }
 }
 
@@ -55,6 +118,13 @@ impl Drop for Ipc {
     }
 }
 
+/// Number of datagrams lost between sequence numbers `last` and `sequence`.
+///
+/// Sequence numbers wrap around after `u32::MAX`.
+const fn lost(last: u32, sequence: u32) -> u32 {
+    sequence.wrapping_sub(last).saturating_sub(1)
+}
+
 /// Bind to datagram socket.
 #[must_use]
 pub fn bind(socket: impl AsRef<Path>) -> Ipc {
@@ -72,3 +142,16 @@ pub fn bind(socket: impl AsRef<Path>) -> Ipc {
         path: path.into(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lost_datagrams() {
+        assert_eq!(lost(1, 2), 0);
+        assert_eq!(lost(1, 5), 3);
+        assert_eq!(lost(u32::MAX, 0), 0);
+        assert_eq!(lost(u32::MAX - 1, 2), 3);
+    }
+}
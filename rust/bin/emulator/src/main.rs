@@ -1,9 +1,12 @@
 //! Light column emulator
 
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
-use tpic6c596::{Emulator, Pin};
+use tpic6c596::{Bits, Emulator, Layout, Packet, Pin, Timing, Vcd, ViolationKind, Wiring};
 
 /// Message sender.
 type Sender = std::sync::mpsc::Sender<Message>;
@@ -13,58 +16,284 @@ type StopSignal = Arc<AtomicBool>;
 
 /// Control message
 #[derive(Debug, Clone, Copy)]
-pub struct Message {
-    /// TPIC6C596 pin
-    pub pin: Pin,
+pub enum Message {
+    /// Pin change.
+    Pin {
+        /// TPIC6C596 pin
+        pin: Pin,
 
-    /// Pin state on/off.
-    pub state: bool,
+        /// Pin state on/off.
+        state: bool,
+
+        /// Time of the change, sent by the client or the arrival time.
+        at: Instant,
+
+        /// Whether `at` reflects the change, and can be timing checked.
+        ///
+        /// Changes batched in a single datagram without times arrive at the same time.
+        timed: bool,
+    },
+
+    /// Datagrams lost, detected through gaps in sequence numbers.
+    Lost(u32),
 }
 
 #[cfg(unix)]
 mod ipc;
 
+mod network;
+
 /// Emulator config
 #[derive(Debug, Parser)]
 struct Config {
     #[cfg(unix)]
     /// Unix Datagram Socket
-    #[arg(short, long, default_value = "/tmp/tpic6c596-emulator.sock")]
+    #[arg(short, long, default_value = tpic6c596::EMULATOR_SOCKET)]
     socket: std::path::PathBuf,
 
     /// Chain length
     #[arg(short, long, default_value_t = 3)]
     chain: usize,
+
+    /// Pin wiring
+    #[arg(short, long, value_enum, default_value_t = WiringArg::Simple)]
+    wiring: WiringArg,
+
+    /// Check pin changes against the datasheet timing and report violations.
+    ///
+    /// Pin changes are checked against the times sent by version 2 and network clients,
+    /// legacy datagrams with a single pin change against their arrival time.
+    /// Frames are clocked in by the emulator and not checked.
+    #[arg(short, long)]
+    timing: bool,
+
+    /// Record pin changes and register outputs to a VCD file.
+    #[arg(short, long)]
+    record: Option<std::path::PathBuf>,
+
+    /// Render lights in logical order using a TOML or JSON layout description.
+    #[arg(short, long)]
+    layout: Option<std::path::PathBuf>,
+
+    /// Also accept network connectors on this TCP address, for example `0.0.0.0:5960`.
+    #[arg(long, requires = "token")]
+    listen: Option<std::net::SocketAddr>,
+
+    /// Shared secret of network connectors.
+    #[arg(long)]
+    token: Option<String>,
+}
+
+/// VCD trace output.
+type Trace = Vcd<std::io::BufWriter<std::fs::File>>;
+
+/// Emulated pin wiring.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum WiringArg {
+    /// Four pin wiring, control high turns outputs on.
+    Simple,
+
+    /// Datasheet wiring with active low G and SRCLR.
+    Datasheet,
+
+    /// Datasheet wiring with RCK tied to SRCK.
+    TiedClocks,
+}
+
+impl From<WiringArg> for Wiring {
+    fn from(value: WiringArg) -> Self {
+        match value {
+            WiringArg::Simple => Self::Simple,
+            WiringArg::Datasheet => Self::Datasheet,
+            WiringArg::TiedClocks => Self::TiedClocks,
+        }
+    }
+}
+
+/// Timing violation and lost datagram counts.
+#[derive(Debug, Default)]
+struct Violations {
+    /// Lost datagrams.
+    lost: u32,
+
+    /// Setup time violations.
+    setup: usize,
+
+    /// Hold time violations.
+    hold: usize,
+
+    /// Pulse width violations.
+    pulse_width: usize,
+}
+
+impl Violations {
+    /// Count violations collected by the emulator.
+    fn collect(&mut self, emulator: &mut Emulator) {
+        for violation in emulator.take_violations() {
+            match violation.kind {
+                ViolationKind::Setup => self.setup += 1,
+                ViolationKind::Hold => self.hold += 1,
+                ViolationKind::PulseWidth => self.pulse_width += 1,
+            }
+        }
+    }
 }
 
 /// Print emulator state
-fn print(emulator: &Emulator) {
+///
+/// With a layout the lights are printed in logical order, eight per group.
+fn print(emulator: &Emulator, layout: Option<&Layout>, violations: &Violations) {
     use std::io::Write;
 
-    print!("\r  State:  ");
-    for register in emulator.registers() {
-        print!(" {:08b}", register.state());
+    if let Some(layout) = layout {
+        let registers: Vec<u8> = emulator.registers().iter().map(|r| r.state()).collect();
+
+        print!("\r  Lights: ");
+        for group in layout.lights(&registers).chunks(8) {
+            print!(" ");
+            for light in group {
+                print!("{}", u8::from(*light));
+            }
+        }
+    } else {
+        print!("\r  State:  ");
+        for register in emulator.registers() {
+            print!(" {:08b}", register.state());
+        }
+    }
+    if emulator.timing().is_some() {
+        print!(
+            "  Violations: setup {}, hold {}, pulse width {}",
+            violations.setup, violations.hold, violations.pulse_width
+        );
+    }
+    if violations.lost > 0 {
+        print!("  Lost: {}", violations.lost);
     }
     std::io::stdout().flush().expect("To flush");
 }
 
 /// Start the emulator.
-fn start_emulator(mut emulator: Emulator, stop: StopSignal) -> Sender {
+fn start_emulator(
+    mut emulator: Emulator,
+    layout: Option<Layout>,
+    mut trace: Option<Trace>,
+    stop: StopSignal,
+) -> Sender {
     let (sender, receiver) = std::sync::mpsc::channel::<Message>();
-    print(&emulator);
+    let mut violations = Violations::default();
+    let start = Instant::now();
+    print(&emulator, layout.as_ref(), &violations);
 
     std::thread::spawn(move || {
         while !stop.load(std::sync::atomic::Ordering::Relaxed) {
-            if let Ok(message) = receiver.recv() {
-                emulator.set_pin(message.pin, message.state);
-                print(&emulator);
+            match receiver.recv() {
+                Ok(Message::Pin {
+                    pin,
+                    state,
+                    at,
+                    timed,
+                }) => {
+                    let at = at.saturating_duration_since(start);
+                    if timed {
+                        emulator.set_pin_at(pin, state, at);
+                    } else {
+                        emulator.set_pin(pin, state);
+                    }
+                    violations.collect(&mut emulator);
+                    if let Some(trace) = &mut trace {
+                        record(trace, &emulator, pin, state, at);
+                    }
+                }
+                Ok(Message::Lost(lost)) => violations.lost += lost,
+                Err(_) => continue,
             }
+            print(&emulator, layout.as_ref(), &violations);
         }
     });
 
     sender
 }
 
+/// Clock of a client, mapping the times of its pin changes onto the emulator clock.
+#[derive(Debug, Default)]
+pub struct ClientClock {
+    /// Emulator time of the client's epoch, set by the first timed packet.
+    epoch: Option<Instant>,
+}
+
+impl ClientClock {
+    /// Emulator time of a change at `time` since the client's epoch.
+    ///
+    /// The clock is anchored on the last change of the first timed packet,
+    /// sent at `last` and received `at`.
+    fn at(&mut self, time: Duration, last: Duration, at: Instant) -> Instant {
+        let epoch = *self
+            .epoch
+            .get_or_insert_with(|| at.checked_sub(last).unwrap_or(at));
+
+        epoch + time
+    }
+}
+
+/// Forward the pin changes of a received packet to the emulator.
+///
+/// Timed pin changes are timing checked against the times sent by the client, see `clock`.
+/// With `per_pin`, packets with a single untimed pin change are checked against their
+/// arrival time `at`. Other batches and frames put many edges on one arrival time,
+/// so they are never checked.
+fn forward(packet: &Packet, at: Instant, per_pin: bool, clock: &mut ClientClock, sender: &Sender) {
+    let send = |pin, state, timed| {
+        let _ = sender.send(Message::Pin {
+            pin,
+            state,
+            at,
+            timed,
+        });
+    };
+
+    match packet {
+        Packet::Pins(pins) => {
+            let timed = per_pin && pins.len() == 1;
+
+            for (pin, state) in pins {
+                send(*pin, *state, timed);
+            }
+        }
+        Packet::TimedPins(pins) => {
+            let last = pins.last().map_or(Duration::ZERO, |(_, _, time)| *time);
+
+            for (pin, state, time) in pins {
+                let _ = sender.send(Message::Pin {
+                    pin: *pin,
+                    state: *state,
+                    at: clock.at(*time, last, at),
+                    timed: true,
+                });
+            }
+        }
+        Packet::Frame { bits, data } => {
+            for index in 0..*bits {
+                send(Pin::Clock, false, false);
+                send(Pin::Data, data.as_slice().bit(index), false);
+                send(Pin::Clock, true, false);
+            }
+            send(Pin::Latch, true, false);
+            send(Pin::Latch, false, false);
+        }
+        Packet::Hello(_) | Packet::Ack | Packet::Auth(_) => {}
+    }
+}
+
+/// Record a pin change and the resulting register outputs.
+fn record(trace: &mut Trace, emulator: &Emulator, pin: Pin, state: bool, at: Duration) {
+    let registers: Vec<u8> = emulator.registers().iter().map(|r| r.state()).collect();
+
+    trace.pin(at, pin, state).expect("To record");
+    trace.registers(at, &registers).expect("To record");
+    trace.flush().expect("To flush recording");
+}
+
 /// Add exit hook
 fn exit_hook(stop: StopSignal) {
     ctrlc::set_handler(move || {
@@ -77,15 +306,50 @@ fn exit_hook(stop: StopSignal) {
 fn main() {
     let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
     let config: Config = Config::parse();
-    let emulator = Emulator::new(config.chain);
+    let mut emulator = Emulator::with_wiring(config.chain, config.wiring.into());
+    if config.timing {
+        emulator = emulator.with_timing(Timing::DATASHEET);
+    }
+    let trace = config.record.as_ref().map(|path| {
+        let file = std::fs::File::create(path).expect("To create recording");
+        Vcd::new(std::io::BufWriter::new(file), config.chain).expect("To write recording")
+    });
+    let layout = config
+        .layout
+        .as_ref()
+        .map(|path| Layout::load(path).expect("To load layout"));
     exit_hook(stop.clone());
 
     println!(
-        "Starting TPIC6C596 shift register emulator\n\n  Socket:  {:#?}\n  Chain:   {}",
-        config.socket, config.chain
+        "Starting TPIC6C596 shift register emulator\n\n  Socket:  {:?}\n  Chain:   {}\n  Wiring:  {:?}\n  Timing:  {}\n  Record:  {}\n  Layout:  {}\n  Listen:  {}",
+        config.socket.display(),
+        config.chain,
+        config.wiring,
+        config.timing,
+        config
+            .record
+            .as_ref()
+            .map_or_else(|| "off".into(), |path| path.display().to_string()),
+        config
+            .layout
+            .as_ref()
+            .map_or_else(|| "off".into(), |path| path.display().to_string()),
+        config
+            .listen
+            .map_or_else(|| "off".into(), |address| address.to_string())
     );
 
-    let sender = start_emulator(emulator, stop.clone());
+    if config.timing {
+        println!("\n  Frames are clocked in by the emulator, their timing is not checked.");
+    }
+
+    let sender = start_emulator(emulator, layout, trace, stop.clone());
+
+    if let (Some(address), Some(token)) = (config.listen, &config.token) {
+        let listener = network::bind(address, token);
+        let sender = sender.clone();
+        std::thread::spawn(move || listener.listen(&sender));
+    }
 
     #[cfg(unix)]
     {
@@ -93,3 +357,44 @@ fn main() {
         ipc.listen(stop, &sender);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_pins() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut clock = ClientClock::default();
+        let arrival = Instant::now();
+        let packet = Packet::TimedPins(vec![
+            (Pin::Clock, true, Duration::from_millis(10)),
+            (Pin::Clock, false, Duration::from_millis(12)),
+        ]);
+
+        forward(&packet, arrival, false, &mut clock, &sender);
+        forward(
+            &packet,
+            arrival + Duration::from_secs(1),
+            false,
+            &mut clock,
+            &sender,
+        );
+
+        let times: Vec<_> = receiver
+            .try_iter()
+            .map(|message| match message {
+                Message::Pin { at, timed, .. } => {
+                    assert!(timed);
+                    at
+                }
+                Message::Lost(_) => panic!("unexpected lost datagrams"),
+            })
+            .collect();
+
+        // Anchored on the last change of the first packet, later packets keep the client times.
+        assert_eq!(times[1], arrival);
+        assert_eq!(times[1] - times[0], Duration::from_millis(2));
+        assert_eq!(times[2..], times[..2]);
+    }
+}
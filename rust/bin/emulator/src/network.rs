@@ -0,0 +1,51 @@
+//! TCP network connector listener
+
+use std::net::SocketAddr;
+
+use tpic6c596::Listener;
+
+use crate::{ClientClock, Sender};
+
+/// Network connectors through TCP.
+#[derive(Debug)]
+pub struct Network {
+    /// Authenticating listener.
+    listener: Listener,
+}
+
+impl Network {
+    /// Accept network connectors, one at a time.
+    ///
+    /// Rejected and failed connections are reported and skipped.
+    pub fn listen(self, sender: &Sender) {
+        loop {
+            let mut session = match self.listener.accept() {
+                Ok(session) => session,
+                Err(error) => {
+                    eprintln!("Rejected network connector: {error}");
+                    continue;
+                }
+            };
+
+            // Streams coalesce writes, arrival times do not reflect the pin changes.
+            let mut clock = ClientClock::default();
+            while let Ok(Some(packet)) = session.receive() {
+                crate::forward(
+                    &packet,
+                    std::time::Instant::now(),
+                    false,
+                    &mut clock,
+                    sender,
+                );
+            }
+        }
+    }
+}
+
+/// Listen for network connectors authenticating with `token`.
+#[must_use]
+pub fn bind(address: SocketAddr, token: &str) -> Network {
+    Network {
+        listener: Listener::bind(address, token).expect("bind the network listener"),
+    }
+}
@@ -16,6 +16,7 @@ pub struct Choreography {
     data: String,
 }
 
+/// Choreography format.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub enum Format {
     /// Python3 light choreography.
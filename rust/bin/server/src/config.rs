@@ -26,6 +26,29 @@ pub struct Config {
     #[arg(short, long, default_value_t = 60.0)]
     timeout: f64,
 
+    /// Mirror choreographies to the emulator listening on this socket.
+    ///
+    /// Passed to choreographies as `TPIC6C596_MIRROR`.
+    #[arg(short, long)]
+    mirror: Option<std::path::PathBuf>,
+
+    /// Record pin changes sent by choreographies to a VCD file.
+    ///
+    /// Changes are still mirrored to the emulator when `--mirror` is set.
+    #[arg(short, long)]
+    record: Option<std::path::PathBuf>,
+
+    /// Turn the lights off when choreographies latch no frame within this many seconds.
+    ///
+    /// The server drives the lights using the connector in `TPIC6C596_CONNECTOR`,
+    /// choreographies send it their pin changes through `TPIC6C596_SERVER`.
+    #[arg(short, long)]
+    watchdog: Option<f64>,
+
+    /// Number of chained shift registers, for the recording and the watchdog.
+    #[arg(long, default_value_t = 3)]
+    chain: usize,
+
     // Web
     /// Bind address
     #[arg(short, long, default_value = "0.0.0.0")]
@@ -57,6 +80,31 @@ impl Config {
         &self.storage
     }
 
+    /// Emulator socket to mirror choreographies to, if any.
+    #[must_use]
+    pub fn mirror(&self) -> Option<&std::path::Path> {
+        self.mirror.as_deref()
+    }
+
+    /// VCD file to record choreographies to, if any.
+    #[must_use]
+    pub fn record(&self) -> Option<&std::path::Path> {
+        self.record.as_deref()
+    }
+
+    /// Longest time between frames before the watchdog turns the lights off, if any.
+    #[must_use]
+    pub fn watchdog(&self) -> Option<Duration> {
+        self.watchdog.map(Duration::from_secs_f64)
+    }
+
+    /// Number of chained shift registers.
+    #[cfg(unix)]
+    #[must_use]
+    pub const fn chain(&self) -> usize {
+        self.chain
+    }
+
     /// Choreography timeout.
     #[must_use]
     pub fn timeout(&self) -> Duration {
@@ -0,0 +1,195 @@
+//! Lights driven by the server on behalf of choreographies.
+
+use std::time::Duration;
+
+use tpic6c596::{
+    AnyConnector, Connector, Controller, ControllerHandle, ControllerThread, Emulator, Error,
+    Packet, Pin, Watchdog, WatchdogEvent, WatchdogThread,
+};
+use tracing::{info, warn};
+
+/// Lights owned by the server and supervised by the watchdog.
+///
+/// Choreographies send their pin changes to the server instead of driving the pins,
+/// so the server is the only one driving the lights. The pin changes are applied to an
+/// emulated chain and every latched frame is written to the lights through the watchdog.
+#[derive(Debug)]
+pub struct Lights {
+    /// Chain emulated from the pin changes, always on so its frame holds the latched outputs.
+    chain: Emulator,
+
+    /// Whether the choreography turned the outputs on.
+    on: bool,
+
+    /// Handle turning the outputs on and off.
+    handle: ControllerHandle,
+
+    /// Watchdog writing the latched frames.
+    watchdog: WatchdogThread<ControllerHandle>,
+
+    /// Thread driving the lights, stopped after the watchdog.
+    _controller: ControllerThread<AnyConnector>,
+}
+
+impl Lights {
+    /// Drive the lights of `controller`, turning them off when no frame
+    /// is latched within `deadline`.
+    #[must_use]
+    pub fn start(controller: Controller<AnyConnector>, deadline: Duration) -> Self {
+        let mut chain = Emulator::new(controller.lights().div_ceil(8));
+        chain.set_pin(Pin::Control, true);
+
+        let controller = ControllerThread::start(controller);
+        let handle = controller.handle();
+        let watchdog = Watchdog::new(controller.handle(), deadline)
+            .on_event(report)
+            .start(Duration::from_millis(100));
+
+        Self {
+            chain,
+            on: handle.is_on(),
+            handle,
+            watchdog,
+            _controller: controller,
+        }
+    }
+
+    /// Apply a packet sent by a choreography.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the controller thread stopped.
+    pub fn apply(&mut self, packet: &Packet) -> Result<(), Error> {
+        match packet {
+            Packet::Pins(pins) => pins
+                .iter()
+                .try_for_each(|(pin, state)| self.set(*pin, *state)),
+            Packet::TimedPins(pins) => pins
+                .iter()
+                .try_for_each(|(pin, state, _)| self.set(*pin, *state)),
+            Packet::Frame { bits, data } => {
+                self.chain.try_shift_frame(&data.as_slice(), *bits)?;
+                self.latched()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply a pin change, the latch commits on its falling edge.
+    fn set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        match pin {
+            Pin::Control if state == self.on => Ok(()),
+            Pin::Control => {
+                self.on = state;
+
+                if state {
+                    self.handle.on()
+                } else {
+                    self.handle.off()
+                }
+            }
+            Pin::Latch if !state && self.chain.get_pin(Pin::Latch) => {
+                self.chain.set_pin(pin, state);
+                self.latched()
+            }
+            _ => {
+                self.chain.set_pin(pin, state);
+                Ok(())
+            }
+        }
+    }
+
+    /// Write the latched frame and restart the deadline.
+    fn latched(&self) -> Result<(), Error> {
+        self.watchdog.try_write(self.chain.frame())
+    }
+}
+
+/// Log watchdog events.
+fn report(event: WatchdogEvent) {
+    match event {
+        WatchdogEvent::Expired { silence } => {
+            warn!("No frames for {silence:?}, turned the lights off");
+        }
+        WatchdogEvent::Recovered => info!("Frames resumed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+    use tpic6c596::{Command, Priority};
+
+    use super::*;
+
+    /// Emulated lights shared with the test.
+    #[derive(Debug, Clone)]
+    struct Shared(Arc<Mutex<Emulator>>);
+
+    impl Shared {
+        /// Lock the emulator.
+        fn lock(&self) -> MutexGuard<'_, Emulator> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    impl Connector for Shared {
+        fn set(&mut self, pin: Pin, state: bool) {
+            self.lock().set_pin(pin, state);
+        }
+
+        fn get(&self, pin: Pin) -> bool {
+            self.lock().get_pin(pin)
+        }
+    }
+
+    /// Pin changes of a choreography turning the outputs on and latching `frame`.
+    fn choreography(chain: usize, frame: u32) -> Vec<(Pin, bool)> {
+        let mut pins = vec![(Pin::Control, true), (Pin::Latch, false)];
+        for bit in 0..chain * 8 {
+            pins.extend([
+                (Pin::Clock, false),
+                (Pin::Data, frame >> bit & 1 != 0),
+                (Pin::Clock, true),
+            ]);
+        }
+        pins.extend([(Pin::Latch, true), (Pin::Latch, false)]);
+
+        pins
+    }
+
+    /// Wait until the queued commands ran, with the outputs on.
+    fn sync(lights: &Lights) {
+        lights
+            .handle
+            .execute(Command::On, Priority::Normal)
+            .expect("on");
+    }
+
+    #[test]
+    fn drive() {
+        let shared = Shared(Arc::new(Mutex::new(Emulator::new(2))));
+        let controller = Controller::connect(AnyConnector::new(shared.clone()), 2);
+        let mut lights = Lights::start(controller, Duration::from_millis(50));
+
+        let pins = choreography(2, 0x8421);
+        let mut reference = Emulator::new(2);
+        for (pin, state) in &pins {
+            reference.set_pin(*pin, *state);
+        }
+
+        lights.apply(&Packet::Pins(pins.clone())).expect("apply");
+        sync(&lights);
+        assert_eq!(shared.lock().registers(), reference.registers());
+
+        std::thread::sleep(Duration::from_millis(400));
+        assert!(lights.watchdog.is_expired());
+        assert!(!shared.lock().is_on());
+
+        lights.apply(&Packet::Pins(pins)).expect("apply");
+        assert!(!lights.watchdog.is_expired());
+        sync(&lights);
+        assert_eq!(shared.lock().registers(), reference.registers());
+    }
+}
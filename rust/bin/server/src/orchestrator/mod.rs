@@ -1,12 +1,18 @@
 //! Orchestrator
 
+#[cfg(unix)]
+mod lights;
+#[cfg(unix)]
+mod monitor;
+
 use std::{
     io::Read,
+    path::PathBuf,
     process::{Child, Command, ExitStatus, Stdio},
     time::Duration,
 };
 
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 use crate::{choreography::Choreography, config::Config};
 
@@ -80,6 +86,13 @@ pub struct Orchestrator {
     /// Choreography timeout. (Not implemented)
     _timeout: Duration,
 
+    /// Emulator socket to mirror choreographies to.
+    mirror: Option<PathBuf>,
+
+    /// Monitor of choreography pin changes.
+    #[cfg(unix)]
+    monitor: Option<monitor::Monitor>,
+
     /// Currently executing choreography process.
     current: Option<Child>,
 
@@ -94,8 +107,25 @@ impl Orchestrator {
         let mut info = Info::new("Startup");
         info.status = Some(ExitStatus::default());
 
+        #[cfg(unix)]
+        let monitor = (config.record().is_some() || config.watchdog().is_some())
+            .then(|| {
+                monitor::Monitor::start(config)
+                    .inspect_err(|error| error!("Failed to start monitoring: {error}"))
+                    .ok()
+            })
+            .flatten();
+
+        #[cfg(not(unix))]
+        if config.record().is_some() || config.watchdog().is_some() {
+            error!("Recording and the watchdog require Unix sockets");
+        }
+
         Self {
             _timeout: config.timeout(),
+            mirror: config.mirror().map(PathBuf::from),
+            #[cfg(unix)]
+            monitor,
             current: None,
             info,
         }
@@ -121,7 +151,23 @@ impl Orchestrator {
 
         // Hardcode python for now
         std::fs::write("run.py", choreography.compile()).expect("write choreography script");
-        let child = Command::new("python3")
+        let mut command = Command::new("python3");
+        let mirror = self.mirror.as_deref();
+        #[cfg(unix)]
+        let mirror = match &self.monitor {
+            // Only the server drives the lights, choreographies send it their pin changes.
+            Some(monitor) if monitor.drives_lights() => {
+                command.env("TPIC6C596_SERVER", monitor.socket());
+                None
+            }
+            Some(monitor) => Some(monitor.socket()),
+            None => mirror,
+        };
+        if let Some(mirror) = mirror {
+            command.env("TPIC6C596_MIRROR", mirror);
+        }
+
+        let child = command
             .current_dir(".")
             .args(["run.py"])
             .stdout(Stdio::piped())
@@ -0,0 +1,296 @@
+//! Monitor of pin changes sent by choreographies.
+
+use std::{
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use tpic6c596::{Connector, Controller, Datagram, Emulator, Packet, Recording};
+use tracing::{error, info, warn};
+
+use super::lights::Lights;
+use crate::config::Config;
+
+/// Recording of the pin changes.
+type Trace = Recording<Emulator>;
+
+/// Monitors pin changes sent by choreographies.
+///
+/// Choreographies send to the monitor socket, which forwards every datagram
+/// to the emulator mirror, if any, and relays its replies to the last choreography
+/// with a bound socket. The pin changes are recorded to a VCD file
+/// when enabled. With the watchdog enabled the server drives the lights,
+/// and choreographies send their pin changes to the monitor instead of driving the pins.
+#[derive(Debug)]
+pub struct Monitor {
+    /// Socket choreographies send to.
+    socket: PathBuf,
+
+    /// Whether the server drives the lights.
+    drives: bool,
+
+    /// Stop signal of the monitoring thread.
+    stop: Arc<AtomicBool>,
+
+    /// Monitoring thread.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Monitor {
+    /// Start monitoring as configured, see `Config::record` and `Config::watchdog`.
+    ///
+    /// Failing to create the recording or to open the connector of the lights is logged,
+    /// and only disables that part.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the socket can not be bound.
+    pub fn start(config: &Config) -> std::io::Result<Self> {
+        let socket =
+            std::env::temp_dir().join(format!("tpic6c596-monitor-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+
+        let datagrams = UnixDatagram::bind(&socket)?;
+        datagrams.set_read_timeout(Some(Duration::from_millis(500)))?;
+
+        let trace = config.record().and_then(|path| {
+            create(path)
+                .and_then(|file| Recording::emulator(Emulator::new(config.chain()), file))
+                .inspect(|_| info!("Recording choreographies to {path:?}"))
+                .inspect_err(|error| error!("Failed to record to {path:?}: {error}"))
+                .ok()
+        });
+
+        let lights = config.watchdog().and_then(|deadline| {
+            Controller::from_env(config.chain())
+                .map(|controller| Lights::start(controller, deadline))
+                .inspect(|_| info!("Turning the lights off after {deadline:?} without frames"))
+                .inspect_err(|error| error!("Failed to drive the lights, no watchdog: {error}"))
+                .ok()
+        });
+
+        let drives = lights.is_some();
+        let mirror = config.mirror().map(Path::to_path_buf);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || monitor(&datagrams, trace, lights, mirror.as_deref(), &stop)
+        });
+
+        Ok(Self {
+            socket,
+            drives,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Socket choreographies send to.
+    #[must_use]
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Whether the server drives the lights, so choreographies must not drive the pins.
+    #[must_use]
+    pub const fn drives_lights(&self) -> bool {
+        self.drives
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+/// Create a buffered recording file.
+fn create(path: &Path) -> std::io::Result<std::io::BufWriter<std::fs::File>> {
+    Ok(std::io::BufWriter::new(std::fs::File::create(path)?))
+}
+
+/// Monitor received datagrams until stopped.
+///
+/// The lights are no longer driven when monitoring stops.
+fn monitor(
+    datagrams: &UnixDatagram,
+    mut trace: Option<Trace>,
+    mut lights: Option<Lights>,
+    mirror: Option<&Path>,
+    stop: &AtomicBool,
+) {
+    let mut buffer = vec![0; 65536];
+
+    // Last choreography able to receive replies of the mirror.
+    let mut client = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        let Ok((received, from)) = datagrams.recv_from(&mut buffer) else {
+            // Flush while idle, so the recording can be inspected.
+            if let Some(trace) = &mut trace {
+                let _ = trace.flush();
+            }
+            continue;
+        };
+
+        match mirror {
+            // Replies of the mirror, like acknowledgements, are relayed to the client.
+            Some(mirror) if from.as_pathname() == Some(mirror) => {
+                if let Some(client) = &client {
+                    let _ = datagrams.send_to_addr(&buffer[..received], client);
+                }
+                continue;
+            }
+            Some(mirror) => {
+                if from.as_pathname().is_some() {
+                    client = Some(from);
+                }
+                let _ = datagrams.send_to(&buffer[..received], mirror);
+            }
+            None => {}
+        }
+
+        let Some(datagram) = Datagram::decode(&buffer[..received]) else {
+            continue;
+        };
+
+        if let Some(lights) = &mut lights {
+            if let Err(error) = lights.apply(&datagram.packet) {
+                warn!("Failed to drive the lights: {error}");
+            }
+        }
+
+        let Some(trace) = &mut trace else {
+            continue;
+        };
+
+        let result = match datagram.packet {
+            Packet::Pins(pins) => pins
+                .into_iter()
+                .try_for_each(|(pin, state)| trace.try_set(pin, state)),
+            Packet::TimedPins(pins) => pins
+                .into_iter()
+                .try_for_each(|(pin, state, _)| trace.try_set(pin, state)),
+            Packet::Frame { bits, data } => trace.try_shift_frame(&data, bits),
+            _ => Ok(()),
+        };
+
+        if let Err(error) = result {
+            warn!("Failed to record: {error}");
+        }
+    }
+
+    if let Some(trace) = &mut trace {
+        let _ = trace.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use tpic6c596::{Pin, PROTOCOL_VERSION};
+
+    use super::*;
+
+    /// Fake framed emulator answering handshakes and acknowledging datagrams.
+    ///
+    /// Returns the received packets once a frame was received or when idle.
+    fn mirror(path: &Path) -> JoinHandle<Vec<Packet>> {
+        let _ = std::fs::remove_file(path);
+        let socket = UnixDatagram::bind(path).expect("bind mirror");
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .expect("read timeout");
+
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            let mut received = Vec::new();
+
+            while let Ok((length, from)) = socket.recv_from(&mut buffer) {
+                let datagram = Datagram::decode(&buffer[..length]).expect("valid datagram");
+                let reply = match datagram.packet {
+                    Packet::Hello(_) => Some(Packet::Hello(PROTOCOL_VERSION)),
+                    _ if datagram.acknowledge => Some(Packet::Ack),
+                    _ => None,
+                };
+
+                if let Some(packet) = reply {
+                    let reply = Datagram {
+                        sequence: datagram.sequence,
+                        acknowledge: false,
+                        packet,
+                    };
+                    let _ = socket.send_to_addr(&reply.encode(), &from);
+                }
+
+                let frame = matches!(datagram.packet, Packet::Frame { .. });
+                received.push(datagram.packet);
+                if frame {
+                    break;
+                }
+            }
+
+            let _ = std::fs::remove_file(&path);
+            received
+        })
+    }
+
+    #[test]
+    fn relay_replies() {
+        let path = std::env::temp_dir().join(format!(
+            "tpic6c596-monitor-mirror-{}.sock",
+            std::process::id()
+        ));
+        let mirror = mirror(&path);
+
+        let config = Config::parse_from(["server", "--mirror", path.to_str().expect("path")]);
+        let monitor = Monitor::start(&config).expect("monitor");
+
+        let mut controller =
+            Controller::emulator_v2_on_socket(monitor.socket(), 1, true).expect("connect");
+        assert_eq!(controller.connector().version(), PROTOCOL_VERSION);
+
+        controller.try_on().expect("acknowledged on");
+        controller.try_write(0xA5_u8).expect("acknowledged write");
+
+        // Times of the pin changes differ between runs.
+        let packets: Vec<_> = mirror
+            .join()
+            .expect("mirror")
+            .into_iter()
+            .map(|packet| match packet {
+                Packet::TimedPins(pins) => Packet::Pins(
+                    pins.into_iter()
+                        .map(|(pin, state, _)| (pin, state))
+                        .collect(),
+                ),
+                packet => packet,
+            })
+            .collect();
+        assert_eq!(
+            packets,
+            [
+                Packet::Hello(PROTOCOL_VERSION),
+                Packet::Pins(vec![(Pin::Latch, false)]),
+                Packet::Pins(vec![(Pin::Control, true)]),
+                Packet::Frame {
+                    bits: 8,
+                    data: vec![0xA5],
+                },
+            ]
+        );
+    }
+}
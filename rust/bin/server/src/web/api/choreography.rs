@@ -79,6 +79,7 @@ pub struct List {
     choreography: Vec<Info>,
 }
 
+/// Choreography info
 #[derive(Debug, serde::Serialize)]
 pub struct Info {
     /// Choreography name.
@@ -53,6 +53,7 @@ pub struct Status {
     log: String,
 }
 
+/// Start request
 #[derive(Debug, serde::Deserialize)]
 pub struct StartRequest {
     /// Choreography to play.
@@ -0,0 +1,446 @@
+//! Connector selected at runtime from a description.
+//!
+//! Descriptions name the connector followed by its arguments, for example
+//! `emulator:/tmp/tpic6c596-emulator.sock` or `rpi:17,22,27,12`,
+//! so tools can share a single connection option instead of a compile time connector.
+
+use std::{path::PathBuf, str::FromStr};
+
+use crate::{Bits, Connector, Controller, Error, Pin};
+
+/// Environment variable with the connector description, see `Controller::from_env`.
+pub const CONNECTOR_ENV: &str = "TPIC6C596_CONNECTOR";
+
+/// Default emulator socket, used when a description has none.
+pub const EMULATOR_SOCKET: &str = "/tmp/tpic6c596-emulator.sock";
+
+/// Errors describing or opening a connector.
+#[derive(Debug)]
+pub enum SpecError {
+    /// The description is empty.
+    Empty,
+
+    /// The description names no known connector.
+    Unknown(String),
+
+    /// The connector is missing a required argument.
+    Missing(&'static str),
+
+    /// The pins are not four numbers, or out of range for the connector.
+    Pins(String),
+
+    /// A pin is used more than once.
+    DuplicatePin(u32),
+
+    /// The network address is not `host:port`.
+    Address(String),
+
+    /// The connector is not compiled in, enable the named feature.
+    Unsupported(&'static str),
+
+    /// Opening the connector failed.
+    Connect(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl std::fmt::Display for SpecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("empty connector description"),
+            Self::Unknown(kind) => write!(f, "unknown connector {kind:?}"),
+            Self::Missing(argument) => write!(f, "connector is missing the {argument}"),
+            Self::Pins(pins) => write!(
+                f,
+                "invalid pins {pins:?}, expected data,clock,latch,control"
+            ),
+            Self::DuplicatePin(pin) => write!(f, "pin {pin} is used twice"),
+            Self::Address(address) => {
+                write!(f, "invalid address {address:?}, expected host:port")
+            }
+            Self::Unsupported(feature) => {
+                write!(f, "connector not supported, enable the {feature} feature")
+            }
+            Self::Connect(error) => write!(f, "failed to open connector: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for SpecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Connect(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed connector description.
+///
+/// | Description                                        | Connector                           |
+/// |----------------------------------------------------|-------------------------------------|
+/// | `emulator` or `emulator:<socket>`                  | `Controller::emulator_on_socket`    |
+/// | `emulator-v2` or `emulator-v2:<socket>`            | `Controller::emulator_v2_on_socket` |
+/// | `rpi:<data>,<clock>,<latch>,<control>`             | `Controller::rpi_gpio`              |
+/// | `gpiocdev:<chip>:<data>,<clock>,<latch>,<control>` | `Controller::gpio_cdev`             |
+/// | `network:<token>@<host>:<port>`                    | `Controller::network`               |
+///
+/// Descriptions parse regardless of the enabled features,
+/// `connect/0` reports connectors that are not compiled in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectorSpec {
+    /// Emulator on a Unix datagram socket.
+    Emulator {
+        /// Emulator socket.
+        socket: PathBuf,
+
+        /// Whether to use the framed protocol, see `Datagram`.
+        framed: bool,
+    },
+
+    /// Raspberry Pi GPIO pins.
+    Rpi {
+        /// Data, clock, latch, and control pin.
+        pins: [u8; 4],
+    },
+
+    /// Linux GPIO character device lines.
+    GpioCdev {
+        /// GPIO chip, for example `/dev/gpiochip0`.
+        chip: PathBuf,
+
+        /// Data, clock, latch, and control line offset.
+        pins: [u32; 4],
+    },
+
+    /// Remote chain attached to a `Listener`.
+    Network {
+        /// Listener address as `host:port`.
+        address: String,
+
+        /// Shared secret.
+        token: String,
+    },
+}
+
+impl FromStr for ConnectorSpec {
+    type Err = SpecError;
+
+    fn from_str(description: &str) -> Result<Self, Self::Err> {
+        let description = description.trim();
+        if description.is_empty() {
+            return Err(SpecError::Empty);
+        }
+
+        let (kind, arguments) = description
+            .split_once(':')
+            .map_or((description, None), |(kind, arguments)| {
+                (kind, Some(arguments))
+            });
+
+        match kind {
+            "emulator" | "emulator-v2" => Ok(Self::Emulator {
+                socket: arguments
+                    .filter(|socket| !socket.is_empty())
+                    .unwrap_or(EMULATOR_SOCKET)
+                    .into(),
+                framed: kind == "emulator-v2",
+            }),
+            "rpi" => {
+                let pins = parse_pins(arguments.ok_or(SpecError::Missing("pins"))?)?;
+                let pins = pins.map(u8::try_from);
+
+                match pins {
+                    [Ok(data), Ok(clock), Ok(latch), Ok(control)] => Ok(Self::Rpi {
+                        pins: [data, clock, latch, control],
+                    }),
+                    _ => Err(SpecError::Pins(arguments.unwrap_or_default().into())),
+                }
+            }
+            "gpiocdev" => {
+                let (chip, pins) = arguments
+                    .and_then(|arguments| arguments.rsplit_once(':'))
+                    .filter(|(chip, _)| !chip.is_empty())
+                    .ok_or(SpecError::Missing("chip"))?;
+
+                Ok(Self::GpioCdev {
+                    chip: chip.into(),
+                    pins: parse_pins(pins)?,
+                })
+            }
+            "network" => {
+                let (token, address) = arguments
+                    .and_then(|arguments| arguments.rsplit_once('@'))
+                    .ok_or(SpecError::Missing("token"))?;
+
+                match address.rsplit_once(':') {
+                    Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                        Ok(Self::Network {
+                            address: address.into(),
+                            token: token.into(),
+                        })
+                    }
+                    _ => Err(SpecError::Address(address.into())),
+                }
+            }
+            kind => Err(SpecError::Unknown(kind.into())),
+        }
+    }
+}
+
+/// Parse four distinct comma separated pins.
+fn parse_pins(pins: &str) -> Result<[u32; 4], SpecError> {
+    let invalid = || SpecError::Pins(pins.into());
+
+    let parsed = pins
+        .split(',')
+        .map(|pin| pin.trim().parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let parsed: [u32; 4] = parsed.try_into().map_err(|_| invalid())?;
+
+    for (index, pin) in parsed.iter().enumerate() {
+        if parsed[..index].contains(pin) {
+            return Err(SpecError::DuplicatePin(*pin));
+        }
+    }
+
+    Ok(parsed)
+}
+
+impl ConnectorSpec {
+    /// Open the described connector.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `SpecError::Unsupported` when the connector is not compiled in,
+    /// or `SpecError::Connect` when opening fails.
+    #[allow(clippy::missing_const_for_fn)] // Only const without any connector.
+    pub fn connect(&self) -> Result<AnyConnector, SpecError> {
+        match self {
+            #[cfg(feature = "connector-emulator")]
+            Self::Emulator { socket, framed } => {
+                let controller = if *framed {
+                    Controller::emulator_v2_on_socket(socket, 0, false)
+                } else {
+                    Controller::emulator_on_socket(socket, 0)
+                };
+
+                Ok(AnyConnector::new(controller.map_err(connect)?.connector))
+            }
+            #[cfg(feature = "connector-rpi")]
+            Self::Rpi {
+                pins: [data, clock, latch, control],
+            } => Ok(AnyConnector::new(
+                Controller::rpi_gpio(*data, *clock, *latch, *control, 0)
+                    .map_err(connect)?
+                    .connector,
+            )),
+            #[cfg(feature = "connector-gpiocdev")]
+            Self::GpioCdev {
+                chip,
+                pins: [data, clock, latch, control],
+            } => Ok(AnyConnector::new(
+                Controller::gpio_cdev(chip, *data, *clock, *latch, *control, 0)
+                    .map_err(connect)?
+                    .connector,
+            )),
+            #[cfg(feature = "connector-network")]
+            Self::Network { address, token } => Ok(AnyConnector::new(
+                crate::Network::connect(address.as_str(), token).map_err(connect)?,
+            )),
+            #[allow(unreachable_patterns)]
+            spec => Err(SpecError::Unsupported(spec.feature())),
+        }
+    }
+
+    /// Feature compiling in the connector.
+    const fn feature(&self) -> &'static str {
+        match self {
+            Self::Emulator { .. } => "connector-emulator",
+            Self::Rpi { .. } => "connector-rpi",
+            Self::GpioCdev { .. } => "connector-gpiocdev",
+            Self::Network { .. } => "connector-network",
+        }
+    }
+}
+
+/// Wrap a connector error.
+#[cfg(any(
+    feature = "connector-emulator",
+    feature = "connector-gpiocdev",
+    feature = "connector-network",
+    feature = "connector-rpi"
+))]
+fn connect(error: impl std::error::Error + Send + Sync + 'static) -> SpecError {
+    SpecError::Connect(Box::new(error))
+}
+
+/// Connector chosen at runtime, see `ConnectorSpec`.
+pub struct AnyConnector(Box<dyn Connector + Send>);
+
+impl AnyConnector {
+    /// Wrap any connector.
+    #[must_use]
+    pub fn new(connector: impl Connector + Send + 'static) -> Self {
+        Self(Box::new(connector))
+    }
+}
+
+impl std::fmt::Debug for AnyConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnyConnector").finish_non_exhaustive()
+    }
+}
+
+impl Connector for AnyConnector {
+    fn get(&self, pin: Pin) -> bool {
+        self.0.get(pin)
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        self.0.set(pin, state);
+    }
+
+    fn try_get(&self, pin: Pin) -> Result<bool, Error> {
+        self.0.try_get(pin)
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.0.try_set(pin, state)
+    }
+
+    fn try_set_batch(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+        self.0.try_set_batch(changes)
+    }
+
+    fn try_set_timed(
+        &mut self,
+        changes: &[(Pin, bool)],
+        at: &[std::time::Instant],
+    ) -> Result<(), Error> {
+        self.0.try_set_timed(changes, at)
+    }
+
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        self.0.try_shift_frame(data, len)
+    }
+
+    fn try_serial_out(&mut self) -> Result<bool, Error> {
+        self.0.try_serial_out()
+    }
+}
+
+impl Controller<AnyConnector> {
+    /// Connect to a TPIC6C596 chain using a connector description, see `ConnectorSpec`.
+    ///
+    /// # Errors
+    ///
+    /// Errors on invalid descriptions, or when the connector can not be opened.
+    pub fn open(description: &str, chain: usize) -> Result<Self, SpecError> {
+        let connector = description.parse::<ConnectorSpec>()?.connect()?;
+
+        Ok(Self::connect(connector, chain))
+    }
+
+    /// Connect using the description in the `TPIC6C596_CONNECTOR` environment variable,
+    /// the default emulator when unset.
+    ///
+    /// # Errors
+    ///
+    /// Errors on invalid descriptions, or when the connector can not be opened.
+    pub fn from_env(chain: usize) -> Result<Self, SpecError> {
+        let description = std::env::var(CONNECTOR_ENV).unwrap_or_else(|_| "emulator".into());
+
+        Self::open(&description, chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parse = |description: &str| description.parse::<ConnectorSpec>();
+
+        assert_eq!(
+            parse("emulator").expect("emulator"),
+            ConnectorSpec::Emulator {
+                socket: EMULATOR_SOCKET.into(),
+                framed: false,
+            }
+        );
+        assert_eq!(
+            parse("emulator-v2:/tmp/x.sock").expect("emulator"),
+            ConnectorSpec::Emulator {
+                socket: "/tmp/x.sock".into(),
+                framed: true,
+            }
+        );
+        assert_eq!(
+            parse("rpi:17,22,27,12").expect("rpi"),
+            ConnectorSpec::Rpi {
+                pins: [17, 22, 27, 12]
+            }
+        );
+        assert_eq!(
+            parse("gpiocdev:/dev/gpiochip0:17, 22, 27, 12").expect("gpiocdev"),
+            ConnectorSpec::GpioCdev {
+                chip: "/dev/gpiochip0".into(),
+                pins: [17, 22, 27, 12]
+            }
+        );
+        assert_eq!(
+            parse("network:s3cr@t@column.local:5960").expect("network"),
+            ConnectorSpec::Network {
+                address: "column.local:5960".into(),
+                token: "s3cr@t".into()
+            }
+        );
+
+        assert!(matches!(parse(" "), Err(SpecError::Empty)));
+        assert!(matches!(parse("spi:0"), Err(SpecError::Unknown(kind)) if kind == "spi"));
+        assert!(matches!(parse("rpi"), Err(SpecError::Missing("pins"))));
+        assert!(matches!(parse("rpi:17,22,27"), Err(SpecError::Pins(_))));
+        assert!(matches!(parse("rpi:17,22,27,300"), Err(SpecError::Pins(_))));
+        assert!(matches!(
+            parse("rpi:17,22,17,12"),
+            Err(SpecError::DuplicatePin(17))
+        ));
+        assert!(matches!(
+            parse("gpiocdev:17,22,27,12"),
+            Err(SpecError::Missing("chip"))
+        ));
+        assert!(matches!(
+            parse("network:localhost:5960"),
+            Err(SpecError::Missing("token"))
+        ));
+        assert!(matches!(
+            parse("network:secret@localhost"),
+            Err(SpecError::Address(_))
+        ));
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn any_connector() {
+        let mut controller = Controller::connect(AnyConnector::new(crate::Emulator::new(2)), 2);
+        controller.on();
+
+        // Chain detection needs pin changes and the serial output forwarded.
+        assert_eq!(controller.detect_chain(4), Some(2));
+        assert!(controller.connector().get(Pin::Control));
+    }
+
+    #[test]
+    fn connect() {
+        let result = Controller::open("emulator:/tmp/tpic6c596-any-test.sock", 1);
+
+        if cfg!(feature = "connector-emulator") {
+            assert!(result.is_ok());
+        } else {
+            assert!(matches!(
+                result,
+                Err(SpecError::Unsupported("connector-emulator"))
+            ));
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Pin change batching.
+//!
+//! Clock and data changes only become visible on the register outputs when latched,
+//! so they can be held back and sent in one go at the end of every latch pulse.
+
+use std::time::Instant;
+
+use crate::{Connector, Controller, Error, Pin, Pins};
+
+/// Largest batch, keeps a batch within a single emulator datagram.
+const BATCH: usize = 1024;
+
+/// Connector batching pin changes of the wrapped connector.
+///
+/// Clock and data changes are held back and sent at once using
+/// `Connector::try_set_timed`, along with the time of every change, when the latch pin goes low or on `flush/0`.
+/// Control, clear, and latch changes are sent immediately, together with the held back changes.
+///
+/// Reduces the per change overhead of connectors like the emulator socket
+/// from one call per pin change to a couple per frame.
+#[derive(Debug)]
+pub struct Batching<C: Connector> {
+    /// Wrapped connector.
+    connector: C,
+
+    /// Held back pin changes, in order.
+    pending: Vec<(Pin, bool)>,
+
+    /// Time of every held back pin change.
+    times: Vec<Instant>,
+
+    /// Latest held back state per pin.
+    state: Pins<Option<bool>>,
+}
+
+impl<C: Connector> Batching<C> {
+    /// Batch pin changes of `connector`.
+    #[must_use]
+    pub fn new(connector: C) -> Self {
+        Self {
+            connector,
+            pending: Vec::with_capacity(BATCH),
+            times: Vec::with_capacity(BATCH),
+            state: Pins::default(),
+        }
+    }
+
+    /// Wrapped connector.
+    #[must_use]
+    pub const fn connector(&self) -> &C {
+        &self.connector
+    }
+
+    /// Number of held back pin changes.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Send all held back pin changes.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the wrapped connector fails to set the pins,
+    /// the held back changes are discarded.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        self.state = Pins::default();
+        let result = self.connector.try_set_timed(&self.pending, &self.times);
+        self.pending.clear();
+        self.times.clear();
+
+        result
+    }
+
+    /// Unwrap the connector.
+    ///
+    /// Held back pin changes are discarded, see `flush/0`.
+    pub fn into_inner(self) -> C {
+        self.connector
+    }
+}
+
+impl<C: Connector> Connector for Batching<C> {
+    fn get(&self, pin: Pin) -> bool {
+        self.state
+            .get(pin)
+            .unwrap_or_else(|| self.connector.get(pin))
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_get(&self, pin: Pin) -> Result<bool, Error> {
+        self.state
+            .get(pin)
+            .map_or_else(|| self.connector.try_get(pin), Ok)
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.pending.push((pin, state));
+        self.times.push(Instant::now());
+        self.state.set(pin, Some(state));
+
+        match pin {
+            Pin::Clock | Pin::Data if self.pending.len() < BATCH => Ok(()),
+            Pin::Latch if state && self.pending.len() < BATCH => Ok(()),
+            _ => self.flush(),
+        }
+    }
+
+    fn try_set_batch(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+        self.flush()?;
+        self.connector.try_set_batch(changes)
+    }
+
+    fn try_set_timed(&mut self, changes: &[(Pin, bool)], at: &[Instant]) -> Result<(), Error> {
+        self.flush()?;
+        self.connector.try_set_timed(changes, at)
+    }
+
+    fn try_serial_out(&mut self) -> Result<bool, Error> {
+        self.flush()?;
+        self.connector.try_serial_out()
+    }
+}
+
+impl<C: Connector> Controller<C> {
+    /// Batch pin changes, see `Batching`.
+    ///
+    /// Keeps the chain length and control state.
+    #[must_use]
+    pub fn batched(self) -> Controller<Batching<C>> {
+        self.map_connector(Batching::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connector recording every call.
+    #[derive(Debug, Default)]
+    struct Calls(Vec<Vec<(Pin, bool)>>);
+
+    impl Connector for Calls {
+        fn set(&mut self, pin: Pin, state: bool) {
+            self.0.push(vec![(pin, state)]);
+        }
+
+        fn get(&self, _pin: Pin) -> bool {
+            false
+        }
+
+        fn try_set_batch(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+            self.0.push(changes.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flush_on_latch() {
+        let mut controller = Controller::connect(Calls::default(), 1).batched();
+        controller.on();
+        controller.write(0b1010_1010_u8);
+
+        let batching = controller.connector();
+        assert_eq!(batching.pending(), 0);
+        assert!(!batching.get(Pin::Latch));
+
+        let calls = &batching.connector().0;
+        assert_eq!(
+            calls.iter().map(Vec::len).collect::<Vec<_>>(),
+            [1, 1, 8 * 3 + 2]
+        );
+        assert_eq!(calls[1], [(Pin::Control, true)]);
+        assert_eq!(
+            calls[2][..3],
+            [(Pin::Clock, false), (Pin::Data, false), (Pin::Clock, true)]
+        );
+        assert_eq!(calls[2][24..], [(Pin::Latch, true), (Pin::Latch, false)]);
+    }
+
+    #[test]
+    fn held_back_state() {
+        let mut batching = Batching::new(Calls::default());
+        batching.set(Pin::Data, true);
+
+        assert!(batching.get(Pin::Data));
+        assert_eq!(batching.try_get(Pin::Clock).ok(), Some(false));
+        assert_eq!(batching.pending(), 1);
+        assert!(batching.connector().0.is_empty());
+
+        batching.flush().expect("flush");
+        assert_eq!(batching.connector().0, [[(Pin::Data, true)]]);
+        assert!(!batching.get(Pin::Data));
+    }
+
+    #[test]
+    fn times() {
+        /// Connector recording the times of every batch.
+        #[derive(Debug, Default)]
+        struct Times(Vec<Vec<Instant>>);
+
+        impl Connector for Times {
+            fn set(&mut self, _pin: Pin, _state: bool) {}
+
+            fn get(&self, _pin: Pin) -> bool {
+                false
+            }
+
+            fn try_set_timed(
+                &mut self,
+                changes: &[(Pin, bool)],
+                at: &[Instant],
+            ) -> Result<(), Error> {
+                assert_eq!(changes.len(), at.len());
+                self.0.push(at.to_vec());
+                Ok(())
+            }
+        }
+
+        let start = Instant::now();
+        let mut controller = Controller::connect(Times::default(), 1).batched();
+        controller.write(0b1010_1010_u8);
+
+        let times = &controller.connector().connector().0;
+        assert_eq!(times.len(), 1);
+        assert_eq!(times[0].len(), 8 * 3 + 2);
+        assert!(times[0][0] >= start);
+        assert!(times[0].is_sorted());
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn emulator() {
+        let mut reference = Controller::connect(crate::Emulator::new(3), 3);
+        let mut batched = Controller::connect(crate::Emulator::new(3), 3).batched();
+
+        for frame in [0x00AA_FF00_u32, 0x0012_3456, 0x00FF_FFFF] {
+            reference.write(frame);
+            batched.write(frame);
+
+            assert_eq!(
+                batched.connector().connector().registers(),
+                reference.connector().registers()
+            );
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Bit sources to shift into the register chain.
+
+/// A sequence of bits that can be shifted into TPIC6C596 shift registers.
+///
+/// Bits are indexed in shift order, meaning bit `0` is shifted in first
+/// and ends up furthest down the register chain.
+///
+/// For byte slices each byte represents a single register,
+/// with bit `n` of the frame stored in `bytes[n / 8]` at position `n % 8`.
+/// This matches the little-endian layout of the integer implementations,
+/// so `0x0102_u16` and `[0x02, 0x01]` describe the same frame.
+///
+/// Indexes past the end of the sequence read as low (0).
+pub trait Bits {
+    /// Get the state of the bit at `index`.
+    #[must_use]
+    fn bit(&self, index: usize) -> bool;
+}
+
+/// Implement `Bits` for primitive integers.
+///
+/// Signed integers use their two's complement representation.
+macro_rules! impl_bits_for_int {
+    ($($int:ty),*) => {
+        $(
+            impl Bits for $int {
+                #[inline]
+                fn bit(&self, index: usize) -> bool {
+                    index < <$int>::BITS as usize && (self >> index) & 1 == 1
+                }
+            }
+        )*
+    };
+}
+
+impl_bits_for_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl Bits for [u8] {
+    #[inline]
+    fn bit(&self, index: usize) -> bool {
+        self.get(index / 8)
+            .is_some_and(|byte| (byte >> (index % 8)) & 1 == 1)
+    }
+}
+
+impl<const N: usize> Bits for [u8; N] {
+    #[inline]
+    fn bit(&self, index: usize) -> bool {
+        self.as_slice().bit(index)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Bits for Vec<u8> {
+    #[inline]
+    fn bit(&self, index: usize) -> bool {
+        self.as_slice().bit(index)
+    }
+}
+
+impl Bits for [bool] {
+    #[inline]
+    fn bit(&self, index: usize) -> bool {
+        self.get(index).copied().unwrap_or(false)
+    }
+}
+
+impl<T: Bits + ?Sized> Bits for &T {
+    #[inline]
+    fn bit(&self, index: usize) -> bool {
+        (**self).bit(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert!(0b101_u8.bit(0));
+        assert!(!0b101_u8.bit(1));
+        assert!(0b101_u8.bit(2));
+        assert!(!u8::MAX.bit(8));
+        assert!(u64::MAX.bit(63));
+        assert!(!u64::MAX.bit(64));
+        assert!(u128::MAX.bit(127));
+        assert!((-1_i32).bit(31));
+        assert!(!(-1_i32).bit(32));
+    }
+
+    #[test]
+    fn bytes_match_integers() {
+        let data: u64 = 0x0F0A_0005_FF00_8001;
+        let bytes = data.to_le_bytes();
+
+        for index in 0..80 {
+            assert_eq!(data.bit(index), bytes.bit(index), "bit {index}");
+            assert_eq!(data.bit(index), bytes.to_vec().bit(index), "bit {index}");
+        }
+    }
+
+    #[test]
+    fn bools() {
+        let bits: &[bool] = &[true, false, true];
+
+        assert!(bits.bit(0));
+        assert!(!bits.bit(1));
+        assert!(bits.bit(2));
+        assert!(!bits.bit(3));
+    }
+}
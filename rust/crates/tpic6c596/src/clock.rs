@@ -0,0 +1,255 @@
+//! Shift clock pacing and rate measurement.
+//!
+//! Waits combine sleeping, for the bulk of long waits, with spinning,
+//! for the last stretch where sleeping is too coarse.
+
+use std::time::{Duration, Instant};
+
+use crate::{Connector, Controller};
+
+/// Remaining wait below which pacing spins instead of sleeping.
+const SPIN: Duration = Duration::from_micros(100);
+
+/// Achieved shift clock statistics of a `Controller`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClockStats {
+    /// Shifted frames, including single bits.
+    pub frames: u64,
+
+    /// Shifted bits.
+    pub bits: u64,
+
+    /// Time spent shifting and latching.
+    pub elapsed: Duration,
+}
+
+impl ClockStats {
+    /// Achieved shift clock frequency in Hz, including the latch overhead.
+    ///
+    /// Zero when nothing was shifted.
+    #[must_use]
+    pub fn frequency(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+
+        if elapsed > 0.0 {
+            #[allow(clippy::cast_precision_loss)]
+            let bits = self.bits as f64;
+            bits / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Shift clock timing and statistics of a `Controller`.
+#[derive(Debug, Default, Clone)]
+pub struct Clocking {
+    /// Target shift clock frequency in Hz, as fast as possible when `None`.
+    pub frequency: Option<u32>,
+
+    /// Minimum duration of every clock level, half the clock period.
+    pub half_period: Duration,
+
+    /// Minimum duration of the latch pulse.
+    pub latch_pulse: Duration,
+
+    /// Achieved clock statistics.
+    pub stats: ClockStats,
+}
+
+impl Clocking {
+    /// Target `frequency` in Hz.
+    pub fn set_frequency(&mut self, frequency: Option<u32>) {
+        self.frequency = frequency;
+        self.half_period = frequency.map_or(Duration::ZERO, |frequency| {
+            Duration::from_nanos(500_000_000 / u64::from(frequency.max(1)))
+        });
+    }
+
+    /// Whether bits are paced by the controller instead of the connector.
+    pub const fn is_paced(&self) -> bool {
+        !self.half_period.is_zero() || !self.latch_pulse.is_zero()
+    }
+
+    /// Hold the current clock level for half a clock period.
+    pub fn hold_clock(&self) {
+        pace(self.half_period);
+    }
+
+    /// Hold the latch pulse.
+    pub fn hold_latch(&self) {
+        pace(self.latch_pulse);
+    }
+
+    /// Record a shifted frame.
+    pub fn record(&mut self, bits: usize, elapsed: Duration) {
+        self.stats.frames += 1;
+        self.stats.bits += bits as u64;
+        self.stats.elapsed += elapsed;
+    }
+}
+
+impl<C: Connector> Controller<C> {
+    /// Pace the shift clock to at most `frequency` Hz.
+    ///
+    /// Every clock level is held for at least half the clock period,
+    /// so the achieved rate stays below the target, see `clock_stats/0`.
+    /// Paced bits are clocked by the controller through `Pin::Data` and `Pin::Clock`
+    /// instead of `Connector::try_shift_frame`.
+    #[must_use]
+    pub fn with_clock_frequency(mut self, frequency: u32) -> Self {
+        self.clocking.set_frequency(Some(frequency));
+        self
+    }
+
+    /// Shift as fast as the connector allows, the default.
+    #[must_use]
+    pub fn with_unpaced_clock(mut self) -> Self {
+        self.clocking.set_frequency(None);
+        self
+    }
+
+    /// Hold `Pin::Latch` high for at least `pulse` when latching.
+    ///
+    /// Like `with_clock_frequency/1`, paces bits through the controller.
+    #[must_use]
+    pub const fn with_latch_pulse(mut self, pulse: Duration) -> Self {
+        self.clocking.latch_pulse = pulse;
+        self
+    }
+
+    /// Target shift clock frequency in Hz, `None` when unpaced.
+    #[must_use]
+    pub const fn clock_frequency(&self) -> Option<u32> {
+        self.clocking.frequency
+    }
+
+    /// Minimum latch pulse duration.
+    #[must_use]
+    pub const fn latch_pulse(&self) -> Duration {
+        self.clocking.latch_pulse
+    }
+
+    /// Achieved shift clock statistics since connecting or the last `reset_clock_stats/0`.
+    #[must_use]
+    pub const fn clock_stats(&self) -> ClockStats {
+        self.clocking.stats
+    }
+
+    /// Reset the shift clock statistics.
+    pub fn reset_clock_stats(&mut self) {
+        self.clocking.stats = ClockStats::default();
+    }
+}
+
+/// Wait for `duration`, sleeping while far from the end and spinning for the rest.
+fn pace(duration: Duration) {
+    if duration.is_zero() {
+        return;
+    }
+
+    let deadline = Instant::now() + duration;
+
+    if let Some(sleep) = duration.checked_sub(SPIN) {
+        std::thread::sleep(sleep);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequency() {
+        let mut clocking = Clocking::default();
+        assert!(!clocking.is_paced());
+
+        clocking.set_frequency(Some(1_000));
+        assert_eq!(clocking.half_period, Duration::from_micros(500));
+        assert!(clocking.is_paced());
+
+        clocking.record(8, Duration::from_millis(4));
+        clocking.record(8, Duration::from_millis(4));
+        assert_eq!(clocking.stats.frames, 2);
+        assert!((clocking.stats.frequency() - 2_000.0).abs() < 1e-9);
+        assert!(ClockStats::default().frequency().abs() < f64::EPSILON);
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn paced_controller() {
+        use crate::{Emulator, Pin, Timing};
+
+        /// Emulator timestamping every pin change, to check the timing.
+        #[derive(Debug)]
+        struct Timed(Emulator, Instant);
+
+        impl Connector for Timed {
+            fn set(&mut self, pin: Pin, state: bool) {
+                self.0.set_pin_at(pin, state, self.1.elapsed());
+            }
+
+            fn get(&self, pin: Pin) -> bool {
+                self.0.get_pin(pin)
+            }
+        }
+
+        let timing = Timing {
+            setup: Duration::from_micros(40),
+            hold: Duration::from_micros(40),
+            pulse_width: Duration::from_micros(40),
+        };
+        let timed = || Timed(Emulator::new(2).with_timing(timing), Instant::now());
+
+        let mut unpaced = Controller::connect(timed(), 2);
+        unpaced.write(0xA5A5_u16);
+        assert!(!unpaced.connector().0.violations().is_empty());
+        assert_eq!(unpaced.clock_stats().bits, 16);
+
+        let mut paced = Controller::connect(timed(), 2)
+            .with_clock_frequency(10_000)
+            .with_latch_pulse(Duration::from_micros(50));
+        paced.on();
+        paced.write(0xA5A5_u16);
+        paced.write(0x5A5A_u16);
+
+        assert_eq!(paced.connector().0.violations(), []);
+        assert_eq!(paced.connector().0.register(1).state(), 0x5A);
+        assert_eq!(paced.clock_frequency(), Some(10_000));
+
+        let stats = paced.clock_stats();
+        assert_eq!((stats.frames, stats.bits), (2, 32));
+        assert!(stats.frequency() < 10_000.0);
+        assert!(stats.elapsed >= Duration::from_micros(32 * 100 + 2 * 50));
+
+        paced.reset_clock_stats();
+        assert_eq!(paced.clock_stats(), ClockStats::default());
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn unpaced_controller() {
+        let mut controller = Controller::connect(crate::Emulator::new(2), 2)
+            .with_clock_frequency(1_000)
+            .with_unpaced_clock();
+        controller.write(0xA5A5_u16);
+        controller.shift_high();
+
+        let stats = controller.clock_stats();
+        assert_eq!(controller.clock_frequency(), None);
+        assert_eq!((stats.frames, stats.bits), (2, 17));
+        assert!(stats.elapsed > Duration::ZERO);
+        assert!(stats.frequency() > 0.0);
+    }
+
+    #[test]
+    fn pacing() {
+        let start = Instant::now();
+        pace(Duration::from_micros(250));
+        pace(Duration::from_micros(20));
+        assert!(start.elapsed() >= Duration::from_micros(270));
+    }
+}
@@ -0,0 +1,160 @@
+//! Connector for `embedded-hal` output pins.
+
+use embedded_hal::digital::{Error as _, OutputPin, PinState};
+
+use crate::{Connector, Error, Pin, Pins};
+
+/// Connector for any four `embedded-hal` output pins.
+///
+/// Builds under `no_std`, for example to drive a chain from a microcontroller.
+/// `Pin::Clear` is not wired and reports `Error::UnsupportedPin`.
+#[derive(Debug)]
+pub struct EmbeddedHal<D: OutputPin, C: OutputPin, L: OutputPin, G: OutputPin> {
+    /// Data pin (SER IN).
+    data: D,
+
+    /// Clock pin (SRCK).
+    clock: C,
+
+    /// Latch pin (RCK).
+    latch: L,
+
+    /// Control pin (G).
+    control: G,
+
+    /// Local pin state.
+    state: Pins<bool>,
+}
+
+impl<D: OutputPin, C: OutputPin, L: OutputPin, G: OutputPin> EmbeddedHal<D, C, L, G> {
+    /// Create a connector from output pins.
+    ///
+    /// The pins are assumed to start low.
+    #[must_use]
+    pub fn new(data: D, clock: C, latch: L, control: G) -> Self {
+        Self {
+            data,
+            clock,
+            latch,
+            control,
+            state: Pins {
+                clear: true,
+                ..Pins::default()
+            },
+        }
+    }
+
+    /// Unwrap the output pins, as (data, clock, latch, control).
+    pub fn into_pins(self) -> (D, C, L, G) {
+        (self.data, self.clock, self.latch, self.control)
+    }
+}
+
+impl<D: OutputPin, C: OutputPin, L: OutputPin, G: OutputPin> Connector for EmbeddedHal<D, C, L, G> {
+    fn get(&self, pin: Pin) -> bool {
+        self.state.get(pin)
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        let level = PinState::from(state);
+
+        let result = match pin {
+            Pin::Data => self.data.set_state(level).map_err(|error| error.kind()),
+            Pin::Clock => self.clock.set_state(level).map_err(|error| error.kind()),
+            Pin::Latch => self.latch.set_state(level).map_err(|error| error.kind()),
+            Pin::Control => self.control.set_state(level).map_err(|error| error.kind()),
+            Pin::Clear => return Err(Error::UnsupportedPin(pin)),
+        };
+        result.map_err(Error::Hal)?;
+        self.state.set(pin, state);
+
+        Ok(())
+    }
+}
+
+impl<D: OutputPin, C: OutputPin, L: OutputPin, G: OutputPin>
+    crate::Controller<EmbeddedHal<D, C, L, G>>
+{
+    /// Connect to a TPIC6C596 chain using `embedded-hal` output pins.
+    ///
+    /// Connector errors are ignored, see `Controller::try_connect`.
+    #[must_use]
+    pub fn embedded_hal(data: D, clock: C, latch: L, control: G, chain: usize) -> Self {
+        Self::connect(EmbeddedHal::new(data, clock, latch, control), chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::{
+        digital::{Mock, State, Transaction},
+        MockError,
+    };
+
+    use crate::{Connector, Controller, Error, Pin};
+
+    /// Expect a pin to be set to the given states, in order.
+    fn pin(states: &[bool]) -> Mock {
+        Mock::new(
+            &states
+                .iter()
+                .map(|state| Transaction::set(if *state { State::High } else { State::Low }))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn write() {
+        let data = pin(&[true, false, true]);
+        let clock = pin(&[false, true, false, true, false, true]);
+        let latch = pin(&[false, true, false]);
+        let control = pin(&[true]);
+
+        let mut controller = Controller::embedded_hal(
+            data.clone(),
+            clock.clone(),
+            latch.clone(),
+            control.clone(),
+            1,
+        );
+        controller.try_on().expect("on");
+        controller.try_shift(0b101_u8, 3).expect("shift");
+        assert!(matches!(
+            controller.try_clear(),
+            Err(Error::UnsupportedPin(Pin::Clear))
+        ));
+
+        for mut pin in [data, clock, latch, control] {
+            pin.done();
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut data = Mock::new(&[]);
+        let mut clock = Mock::new(&[
+            Transaction::set(State::Low).with_error(MockError::Io(std::io::ErrorKind::Other))
+        ]);
+        let mut latch = pin(&[false]);
+        let mut control = Mock::new(&[]);
+
+        let mut controller = Controller::embedded_hal(
+            data.clone(),
+            clock.clone(),
+            latch.clone(),
+            control.clone(),
+            1,
+        );
+        assert!(matches!(controller.try_write(0), Err(Error::Hal(_))));
+        assert!(!controller.connector().get(Pin::Clock));
+
+        data.done();
+        clock.done();
+        latch.done();
+        control.done();
+    }
+}
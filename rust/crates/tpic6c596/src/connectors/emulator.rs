@@ -1,8 +1,25 @@
 //! Emulator connector.
 
-use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::{
+    os::unix::net::{SocketAddr, UnixDatagram},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
 
-use crate::{Connector, Pin, Pins};
+use crate::{Bits, Connector, Datagram, Error, Packet, Pin, Pins, PROTOCOL_VERSION};
+
+/// Time to wait for the emulator to answer a handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Time to wait for an acknowledgement before resending.
+const ACK_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Number of resends of an unacknowledged datagram.
+const RETRIES: usize = 3;
+
+/// Counter for unique reply socket paths.
+static REPLY_SOCKETS: AtomicUsize = AtomicUsize::new(0);
 
 ///  Emulator connector.
 #[derive(Debug)]
@@ -15,6 +32,129 @@ pub struct Emulator {
 
     /// Local pin state.
     state: Pins<bool>,
+
+    /// Negotiated protocol version, `1` for the legacy format.
+    version: u8,
+
+    /// Sequence number of the last sent datagram.
+    sequence: u32,
+
+    /// Request acknowledgements for every datagram.
+    acknowledge: bool,
+
+    /// Path of the bound reply socket, removed on drop.
+    reply: Option<PathBuf>,
+
+    /// Epoch of the times of `Packet::TimedPins`.
+    epoch: Instant,
+}
+
+impl Emulator {
+    /// Send a packet using the framed protocol.
+    ///
+    /// Waits for the acknowledgement when requested, resending lost datagrams.
+    fn send(&mut self, packet: Packet) -> Result<(), Error> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let datagram = Datagram {
+            sequence: Some(self.sequence),
+            acknowledge: self.acknowledge,
+            packet,
+        };
+        let bytes = datagram.encode();
+
+        for _ in 0..=RETRIES {
+            self.socket.send_to_addr(&bytes, &self.address)?;
+
+            if !self.acknowledge || self.acknowledged(self.sequence)? {
+                return Ok(());
+            }
+        }
+
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "emulator did not acknowledge",
+        )))
+    }
+
+    /// Wait for the acknowledgement of `sequence`, ignoring stale acknowledgements.
+    fn acknowledged(&self, sequence: u32) -> Result<bool, Error> {
+        let mut buffer = [0; 64];
+
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(received) => {
+                    if let Some(Datagram {
+                        sequence: Some(acked),
+                        packet: Packet::Ack,
+                        ..
+                    }) = Datagram::decode(&buffer[..received])
+                    {
+                        if acked == sequence {
+                            return Ok(true);
+                        }
+                    }
+                }
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(false);
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+
+    /// Negotiate the protocol version, falling back to the legacy format without answer.
+    fn handshake(&mut self) -> std::io::Result<()> {
+        let hello = Datagram {
+            sequence: Some(0),
+            acknowledge: false,
+            packet: Packet::Hello(PROTOCOL_VERSION),
+        };
+        self.socket.send_to_addr(&hello.encode(), &self.address)?;
+
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        let mut buffer = [0; 64];
+        self.version = 1;
+
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            self.socket
+                .set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+
+            match self.socket.recv(&mut buffer) {
+                Ok(received) => {
+                    if let Some(Datagram {
+                        packet: Packet::Hello(version),
+                        ..
+                    }) = Datagram::decode(&buffer[..received])
+                    {
+                        self.version = version.min(PROTOCOL_VERSION);
+                        break;
+                    }
+                }
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.socket.set_read_timeout(Some(ACK_TIMEOUT))
+    }
+
+    /// Negotiated protocol version, `1` for the legacy format.
+    #[must_use]
+    pub const fn version(&self) -> u8 {
+        self.version
+    }
 }
 
 impl Connector for Emulator {
@@ -23,17 +163,80 @@ impl Connector for Emulator {
     }
 
     fn set(&mut self, pin: Pin, state: bool) {
-        self.state.set(pin, state);
-
-        let state = if state { 0b1000_0000_u8 } else { 0 };
-        let byte = match pin {
-            Pin::Data => state | 1,
-            Pin::Control => state | 2,
-            Pin::Clock => state | 3,
-            Pin::Latch => state | 4,
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.try_set_timed(&[(pin, state)], &[Instant::now()])
+    }
+
+    fn try_set_batch(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+        for (pin, state) in changes {
+            self.state.set(*pin, *state);
+        }
+
+        if self.version >= 2 {
+            return self.send(Packet::Pins(changes.to_vec()));
+        }
+
+        let legacy = Datagram {
+            sequence: None,
+            acknowledge: false,
+            packet: Packet::Pins(changes.to_vec()),
         };
+        self.socket.send_to_addr(&legacy.encode(), &self.address)?;
+
+        Ok(())
+    }
+
+    fn try_set_timed(&mut self, changes: &[(Pin, bool)], at: &[Instant]) -> Result<(), Error> {
+        if self.version < 2 {
+            return self.try_set_batch(changes);
+        }
+
+        for (pin, state) in changes {
+            self.state.set(*pin, *state);
+        }
+
+        self.send(Packet::TimedPins(
+            changes
+                .iter()
+                .zip(at)
+                .map(|((pin, state), at)| (*pin, *state, at.saturating_duration_since(self.epoch)))
+                .collect(),
+        ))
+    }
+
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        if self.version < 2 {
+            return crate::clock_frame(self, data, len);
+        }
+
+        let mut bytes = vec![0; len.div_ceil(8)];
+        for index in (0..len).filter(|index| data.bit(*index)) {
+            bytes[index / 8] |= 1 << (index % 8);
+        }
+
+        self.send(Packet::Frame {
+            bits: len,
+            data: bytes,
+        })?;
+
+        if len > 0 {
+            self.state.set(Pin::Clock, true);
+            self.state.set(Pin::Data, data.bit(len - 1));
+        }
+        self.state.set(Pin::Latch, false);
+
+        Ok(())
+    }
+}
 
-        let _ = self.socket.send_to_addr(&[byte], &self.address);
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        if let Some(reply) = &self.reply {
+            let _ = std::fs::remove_file(reply);
+        }
     }
 }
 
@@ -44,7 +247,7 @@ impl crate::Controller<Emulator> {
     ///
     /// Errors on invalid socket address or failure to creates a Unix Datagram socket.
     pub fn emulator(chain: usize) -> std::io::Result<Self> {
-        Self::emulator_on_socket("/tmp/tpic6c596-emulator.sock", chain)
+        Self::emulator_on_socket(crate::EMULATOR_SOCKET, chain)
     }
 
     /// Connect to a TPIC6C596 chain emulator on a specific socket.
@@ -61,8 +264,233 @@ impl crate::Controller<Emulator> {
                 socket: UnixDatagram::unbound()?,
                 address: SocketAddr::from_pathname(socket)?,
                 state: Pins::default(),
+                version: 1,
+                sequence: 0,
+                acknowledge: false,
+                reply: None,
+                epoch: Instant::now(),
             },
             chain,
         ))
     }
+
+    /// Connect to a TPIC6C596 chain emulator on a specific socket using the framed protocol.
+    ///
+    /// Negotiates the protocol version with the emulator and falls back to the
+    /// legacy format when the emulator does not answer.
+    /// Frames are sent as a single datagram, see `Packet::Frame`,
+    /// and pin changes along with their time, see `Packet::TimedPins`.
+    /// With `acknowledge`, every datagram is acknowledged by the emulator
+    /// and resent when lost.
+    ///
+    /// # Errors
+    ///
+    /// Errors on invalid socket address, failure to create or bind a Unix Datagram socket,
+    /// or when the emulator is not running.
+    pub fn emulator_v2_on_socket(
+        socket: impl AsRef<std::path::Path>,
+        chain: usize,
+        acknowledge: bool,
+    ) -> std::io::Result<Self> {
+        let reply = std::env::temp_dir().join(format!(
+            "tpic6c596-client-{}-{}.sock",
+            std::process::id(),
+            REPLY_SOCKETS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&reply);
+
+        let mut emulator = Emulator {
+            socket: UnixDatagram::bind(&reply)?,
+            address: SocketAddr::from_pathname(socket)?,
+            state: Pins::default(),
+            version: 1,
+            sequence: 0,
+            acknowledge,
+            reply: Some(reply),
+            epoch: Instant::now(),
+        };
+        emulator.handshake()?;
+
+        Ok(Self::connect(emulator, chain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixDatagram, path::PathBuf, thread::JoinHandle, time::Duration};
+
+    use crate::{Connector, Controller, Datagram, Error, Packet, Pin, PROTOCOL_VERSION};
+
+    #[test]
+    fn disconnected() {
+        // Socket of an emulator that stopped, the path exists but nobody is bound to it.
+        let path = std::env::temp_dir().join(format!(
+            "tpic6c596-emulator-stopped-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        drop(UnixDatagram::bind(&path).expect("bind stopped emulator"));
+
+        let mut controller = Controller::emulator_on_socket(&path, 3).expect("unbound socket");
+
+        assert!(matches!(controller.try_write(0), Err(Error::Disconnected)));
+        assert!(matches!(controller.try_on(), Err(Error::Disconnected)));
+
+        // Infallible layer ignores the error.
+        controller.write(0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn missing_socket() {
+        let mut controller =
+            Controller::emulator_on_socket("/tmp/tpic6c596-emulator-missing.sock", 3)
+                .expect("unbound socket");
+
+        assert!(matches!(
+            controller.try_on(),
+            Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    /// Fake emulator answering handshakes and acknowledging datagrams.
+    ///
+    /// Ignores the first `drop` acknowledged datagrams,
+    /// returns all received datagrams once `count` were received or when idle.
+    fn fake(
+        name: &str,
+        version: Option<u8>,
+        drop: usize,
+        count: usize,
+    ) -> (PathBuf, JoinHandle<Vec<Datagram>>) {
+        let path =
+            std::env::temp_dir().join(format!("tpic6c596-fake-{name}-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).expect("bind fake emulator");
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .expect("read timeout");
+
+        let cleanup = path.clone();
+        let thread = std::thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            let mut received = Vec::new();
+            let mut dropped = 0;
+
+            while received.len() < count {
+                let Ok((length, from)) = socket.recv_from(&mut buffer) else {
+                    break;
+                };
+                let datagram = Datagram::decode(&buffer[..length]).expect("valid datagram");
+                let reply = |packet| {
+                    let datagram = Datagram {
+                        sequence: datagram.sequence,
+                        acknowledge: false,
+                        packet,
+                    };
+                    let _ = socket.send_to_addr(&datagram.encode(), &from);
+                };
+
+                match (&datagram.packet, version) {
+                    (Packet::Hello(_), None) => continue,
+                    (Packet::Hello(_), Some(version)) => reply(Packet::Hello(version)),
+                    (_, _) if datagram.acknowledge && dropped < drop => {
+                        dropped += 1;
+                        continue;
+                    }
+                    (_, _) if datagram.acknowledge => reply(Packet::Ack),
+                    (_, _) => {}
+                }
+
+                received.push(datagram);
+            }
+
+            let _ = std::fs::remove_file(&cleanup);
+            received
+        });
+
+        (path, thread)
+    }
+
+    #[test]
+    fn framed() {
+        let (path, fake) = fake("framed", Some(PROTOCOL_VERSION), 1, 4);
+        let mut controller = Controller::emulator_v2_on_socket(path, 2, true).expect("connect");
+        assert_eq!(controller.connector().version(), 2);
+
+        controller.try_on().expect("on");
+        controller.try_write(0x0180_u16).expect("write");
+
+        let received = fake.join().expect("fake emulator");
+        let times: Vec<_> = received
+            .iter()
+            .filter_map(|datagram| match &datagram.packet {
+                Packet::TimedPins(pins) => Some(pins[0].2),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(times.len(), 2);
+        assert!(times[0] <= times[1]);
+
+        let packets: Vec<_> = received
+            .iter()
+            .map(|datagram| match &datagram.packet {
+                Packet::TimedPins(pins) => Packet::TimedPins(
+                    pins.iter()
+                        .map(|(pin, state, _)| (*pin, *state, Duration::ZERO))
+                        .collect(),
+                ),
+                packet => packet.clone(),
+            })
+            .collect();
+        assert_eq!(
+            packets,
+            [
+                Packet::Hello(PROTOCOL_VERSION),
+                Packet::TimedPins(vec![(Pin::Latch, false, Duration::ZERO)]),
+                Packet::TimedPins(vec![(Pin::Control, true, Duration::ZERO)]),
+                Packet::Frame {
+                    bits: 16,
+                    data: vec![0x80, 0x01],
+                },
+            ]
+        );
+        assert_eq!(received[3].sequence, Some(3));
+        assert!(controller.connector().get(Pin::Clock));
+        assert!(!controller.connector().get(Pin::Latch));
+    }
+
+    #[test]
+    fn legacy_fallback() {
+        let (path, fake) = fake("legacy", None, 0, 2);
+        let mut controller = Controller::emulator_v2_on_socket(path, 1, true).expect("connect");
+        assert_eq!(controller.connector().version(), 1);
+
+        controller.try_on().expect("on");
+
+        let received = fake.join().expect("fake emulator");
+        assert!(received.iter().all(|datagram| datagram.sequence.is_none()));
+    }
+
+    #[test]
+    fn unacknowledged() {
+        let (path, fake) = fake("unacknowledged", Some(PROTOCOL_VERSION), usize::MAX, 5);
+        let mut controller = Controller::emulator_v2_on_socket(path, 1, true).expect("connect");
+
+        assert!(matches!(
+            controller.try_on(),
+            Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::TimedOut
+        ));
+        drop(controller);
+
+        let received = fake.join().expect("fake emulator");
+        assert_eq!(
+            received,
+            [Datagram {
+                sequence: Some(0),
+                acknowledge: false,
+                packet: Packet::Hello(PROTOCOL_VERSION),
+            }]
+        );
+    }
 }
@@ -0,0 +1,436 @@
+//! Linux GPIO character device connector using `gpio-cdev` crate.
+
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+
+use crate::{Connector, Error, Pin, Pins};
+
+/// Consumer label registered with the kernel for requested lines.
+const CONSUMER: &str = "tpic6c596";
+
+/// Output line driven by the `GpioCdev` connector.
+///
+/// Implemented for `gpio_cdev::LineHandle`,
+/// but can be implemented to inject a fake GPIO chip.
+pub trait OutputLine {
+    /// Drive the line high (`true`) or low (`false`).
+    ///
+    /// # Errors
+    ///
+    /// Errors when the line value could not be set.
+    fn set_value(&mut self, high: bool) -> Result<(), Error>;
+}
+
+impl OutputLine for LineHandle {
+    fn set_value(&mut self, high: bool) -> Result<(), Error> {
+        Self::set_value(self, u8::from(high))
+            .map_err(|error| Error::Io(std::io::Error::other(error)))
+    }
+}
+
+/// Input line reading the serial output (SER OUT) for the `GpioCdev` connector.
+///
+/// Implemented for `gpio_cdev::LineHandle` requested as input,
+/// but can be implemented to inject a fake GPIO chip.
+pub trait InputLine: std::fmt::Debug {
+    /// Read whether the line is high (`true`) or low (`false`).
+    ///
+    /// # Errors
+    ///
+    /// Errors when the line value could not be read.
+    fn value(&mut self) -> Result<bool, Error>;
+}
+
+impl InputLine for LineHandle {
+    fn value(&mut self) -> Result<bool, Error> {
+        self.get_value()
+            .map(|value| value != 0)
+            .map_err(|error| Error::Io(std::io::Error::other(error)))
+    }
+}
+
+/// Linux GPIO character device connector using `gpio-cdev` crate.
+///
+/// Drives the pins through line requests on `/dev/gpiochipN`,
+/// making it usable on any Linux board exposing GPIO.
+/// `Pin::Clear` is only available when wired using `with_clear/1`,
+/// the serial output when wired using `with_serial_out/1`.
+///
+/// Parallel chains get a data line each using `with_data_line/1`,
+/// see `MultiController`. `Pin::Data` then sets all data lines.
+#[derive(Debug)]
+pub struct GpioCdev<L: OutputLine = LineHandle> {
+    /// Output lines, `None` when not wired.
+    lines: Pins<Option<L>>,
+
+    /// Data lines of additional parallel chains.
+    data: Vec<L>,
+
+    /// Local pin state.
+    state: Pins<bool>,
+
+    /// Serial output (SER OUT) input line, `None` when not wired.
+    serial_out: Option<Box<dyn InputLine + Send>>,
+}
+
+impl<L: OutputLine> GpioCdev<L> {
+    /// Create a connector from already requested output lines.
+    ///
+    /// The lines are assumed to start low.
+    #[must_use]
+    pub fn from_lines(data: L, clock: L, latch: L, control: L) -> Self {
+        Self {
+            lines: Pins {
+                clear: None,
+                clock: Some(clock),
+                control: Some(control),
+                data: Some(data),
+                latch: Some(latch),
+            },
+            data: Vec::new(),
+            state: Pins::default(),
+            serial_out: None,
+        }
+    }
+
+    /// Wire the data line of an additional parallel chain, see `MultiController`.
+    ///
+    /// The line is assumed to start low.
+    #[must_use]
+    pub fn with_data_line(mut self, data: L) -> Self {
+        self.data.push(data);
+        self
+    }
+
+    /// Wire the shift register clear line (SRCLR).
+    ///
+    /// The line is assumed to start high (inactive).
+    #[must_use]
+    pub fn with_clear(mut self, clear: L) -> Self {
+        self.lines.clear = Some(clear);
+        self.state.clear = true;
+        self
+    }
+
+    /// Wire the serial output (SER OUT) of the last register, see `Controller::detect_chain`.
+    #[must_use]
+    pub fn with_serial_out(mut self, serial_out: impl InputLine + Send + 'static) -> Self {
+        self.serial_out = Some(Box::new(serial_out));
+        self
+    }
+}
+
+impl<L: OutputLine> Connector for GpioCdev<L> {
+    fn get(&self, pin: Pin) -> bool {
+        self.state.get(pin)
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.lines
+            .get_mut(pin)
+            .as_mut()
+            .ok_or(Error::UnsupportedPin(pin))?
+            .set_value(state)?;
+        self.state.set(pin, state);
+
+        if pin == Pin::Data {
+            for line in &mut self.data {
+                line.set_value(state)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn try_serial_out(&mut self) -> Result<bool, Error> {
+        self.serial_out.as_mut().ok_or(Error::NoSerialOut)?.value()
+    }
+}
+
+impl<L: OutputLine> crate::MultiConnector for GpioCdev<L> {
+    fn data_pins(&self) -> usize {
+        1 + self.data.len()
+    }
+
+    fn try_set_data(&mut self, states: &[bool]) -> Result<(), Error> {
+        let mut states = states.iter().copied();
+
+        if let Some(state) = states.next() {
+            self.lines
+                .data
+                .as_mut()
+                .ok_or(Error::UnsupportedPin(Pin::Data))?
+                .set_value(state)?;
+            self.state.data = state;
+        }
+
+        for (line, state) in self.data.iter_mut().zip(states) {
+            line.set_value(state)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl crate::Controller<GpioCdev> {
+    /// Connect to a TPIC6C596 chain using a Linux GPIO character device.
+    ///
+    /// Pins are given as line offsets on the `chip`, for example `/dev/gpiochip0`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the chip can not be opened or a line can not be requested.
+    pub fn gpio_cdev(
+        chip: impl AsRef<std::path::Path>,
+        data_pin: u32,
+        clock_pin: u32,
+        latch_pin: u32,
+        control_pin: u32,
+        chain: usize,
+    ) -> Result<Self, gpio_cdev::Error> {
+        let mut chip = Chip::new(chip)?;
+        let mut request = |offset| {
+            chip.get_line(offset)?
+                .request(LineRequestFlags::OUTPUT, 0, CONSUMER)
+        };
+
+        Ok(Self::connect(
+            GpioCdev::from_lines(
+                request(data_pin)?,
+                request(clock_pin)?,
+                request(latch_pin)?,
+                request(control_pin)?,
+            ),
+            chain,
+        ))
+    }
+}
+
+impl crate::MultiController<GpioCdev> {
+    /// Connect to parallel TPIC6C596 chains using a Linux GPIO character device.
+    ///
+    /// Pins are given as line offsets on the `chip`, with a data pin per chain
+    /// and `chains[n]` registers in chain `n`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the chip can not be opened or a line can not be requested.
+    ///
+    /// # Panics
+    ///
+    /// Panics when there are no chains,
+    /// or when the number of data pins differs from the number of chains.
+    pub fn gpio_cdev(
+        chip: impl AsRef<std::path::Path>,
+        data_pins: &[u32],
+        clock_pin: u32,
+        latch_pin: u32,
+        control_pin: u32,
+        chains: &[usize],
+    ) -> Result<Self, gpio_cdev::Error> {
+        assert!(!chains.is_empty(), "at least one chain");
+        assert_eq!(data_pins.len(), chains.len(), "a data pin per chain");
+
+        let mut chip = Chip::new(chip)?;
+        let mut request = |offset| {
+            chip.get_line(offset)?
+                .request(LineRequestFlags::OUTPUT, 0, CONSUMER)
+        };
+
+        let mut connector = GpioCdev::from_lines(
+            request(data_pins[0])?,
+            request(clock_pin)?,
+            request(latch_pin)?,
+            request(control_pin)?,
+        );
+        for data_pin in &data_pins[1..] {
+            connector = connector.with_data_line(request(*data_pin)?);
+        }
+
+        Ok(Self::connect(connector, chains))
+    }
+}
+
+impl<L: OutputLine> crate::Controller<GpioCdev<L>> {
+    /// Wire the serial output (SER OUT) of the last register, see `detect_chain/1`.
+    ///
+    /// For example a line requested using `LineRequestFlags::INPUT`.
+    #[must_use]
+    pub fn with_serial_out(mut self, serial_out: impl InputLine + Send + 'static) -> Self {
+        self.connector = self.connector.with_serial_out(serial_out);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fake line writing values to a shared list of changes.
+    #[derive(Debug)]
+    struct FakeLine {
+        /// Pin represented by this line.
+        pin: Pin,
+
+        /// Recorded pin changes.
+        changes: std::rc::Rc<std::cell::RefCell<Vec<(Pin, bool)>>>,
+    }
+
+    impl OutputLine for FakeLine {
+        fn set_value(&mut self, high: bool) -> Result<(), Error> {
+            self.changes.borrow_mut().push((self.pin, high));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn injected_lines() {
+        let changes = std::rc::Rc::default();
+        let line = |pin| FakeLine {
+            pin,
+            changes: std::rc::Rc::clone(&changes),
+        };
+
+        let connector = GpioCdev::from_lines(
+            line(Pin::Data),
+            line(Pin::Clock),
+            line(Pin::Latch),
+            line(Pin::Control),
+        );
+        let mut controller = crate::Controller::connect(connector, 1);
+        controller.on();
+        controller.shift_high();
+
+        assert!(controller.connector().get(Pin::Control));
+        assert!(controller.connector().get(Pin::Data));
+        assert_eq!(
+            *changes.borrow(),
+            [
+                (Pin::Latch, false),
+                (Pin::Control, true),
+                (Pin::Clock, false),
+                (Pin::Data, true),
+                (Pin::Clock, true),
+                (Pin::Latch, true),
+                (Pin::Latch, false),
+            ]
+        );
+    }
+
+    /// Fake input line reading a fixed value.
+    #[derive(Debug)]
+    struct FixedLine(bool);
+
+    impl InputLine for FixedLine {
+        fn value(&mut self) -> Result<bool, Error> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn serial_out() {
+        let changes = std::rc::Rc::default();
+        let line = |pin| FakeLine {
+            pin,
+            changes: std::rc::Rc::clone(&changes),
+        };
+        let connector = || {
+            GpioCdev::from_lines(
+                line(Pin::Data),
+                line(Pin::Clock),
+                line(Pin::Latch),
+                line(Pin::Control),
+            )
+        };
+
+        let mut unwired = crate::Controller::connect(connector(), 2);
+        assert!(matches!(
+            unwired.try_detect_chain(4),
+            Err(Error::NoSerialOut)
+        ));
+
+        // A serial output stuck low never sees the probe.
+        let mut stuck =
+            crate::Controller::connect(connector(), 2).with_serial_out(FixedLine(false));
+        assert_eq!(stuck.try_detect_chain(4).ok(), Some(None));
+        assert_eq!(stuck.register_chain(), 2);
+    }
+
+    /// Fake line driving a pin of emulated chains.
+    #[cfg(feature = "emulator")]
+    #[derive(Debug)]
+    struct EmulatedLine {
+        /// Pin driven by this line.
+        pin: Pin,
+
+        /// Chain driven by this line, `None` for all chains.
+        chain: Option<usize>,
+
+        /// Emulated chains.
+        chains: std::rc::Rc<std::cell::RefCell<Vec<crate::Emulator>>>,
+    }
+
+    #[cfg(feature = "emulator")]
+    impl OutputLine for EmulatedLine {
+        fn set_value(&mut self, high: bool) -> Result<(), Error> {
+            for (index, chain) in self.chains.borrow_mut().iter_mut().enumerate() {
+                if self.chain.is_none_or(|chain| chain == index) {
+                    chain.set_pin(self.pin, high);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn parallel_chains() {
+        let chains = [2, 1];
+        let emulators = std::rc::Rc::new(std::cell::RefCell::new(
+            chains
+                .iter()
+                .map(|chain| crate::Emulator::new(*chain))
+                .collect(),
+        ));
+        let line = |pin, chain| EmulatedLine {
+            pin,
+            chain,
+            chains: std::rc::Rc::clone(&emulators),
+        };
+
+        let connector = GpioCdev::from_lines(
+            line(Pin::Data, Some(0)),
+            line(Pin::Clock, None),
+            line(Pin::Latch, None),
+            line(Pin::Control, None),
+        )
+        .with_data_line(line(Pin::Data, Some(1)));
+        let mut controller = crate::MultiController::connect(connector, &chains);
+        controller.on();
+        controller.write(&[0xA55A_u16, 0x0081]);
+
+        let emulators = emulators.borrow();
+        assert_eq!(emulators[0].register(0).state(), 0xA5);
+        assert_eq!(emulators[0].register(1).state(), 0x5A);
+        assert_eq!(emulators[1].register(0).state(), 0x81);
+    }
+
+    /// Run against a real (or simulated) chip.
+    ///
+    /// Load `gpio-sim` or `gpio-mockup` (`modprobe gpio-mockup gpio_mockup_ranges=-1,4`)
+    /// and point `TPIC6C596_GPIOCHIP` at the created chip.
+    #[test]
+    #[ignore = "requires a (simulated) GPIO chip"]
+    fn chip() {
+        let chip = std::env::var("TPIC6C596_GPIOCHIP").expect("TPIC6C596_GPIOCHIP set");
+        let mut controller =
+            crate::Controller::gpio_cdev(chip, 0, 1, 2, 3, 3).expect("request lines");
+
+        controller.try_on().expect("turn on");
+        controller.try_write(0b1010_1010).expect("write");
+        controller.try_off().expect("turn off");
+    }
+}
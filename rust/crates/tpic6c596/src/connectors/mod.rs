@@ -1,7 +1,27 @@
 //! TPIC6C596 connectors
 
+#[cfg(feature = "connector-embedded-hal")]
+mod embedded_hal;
+#[cfg(feature = "connector-embedded-hal")]
+pub use embedded_hal::EmbeddedHal;
+
 #[cfg(feature = "connector-emulator")]
 mod emulator;
 
+#[cfg(feature = "connector-gpiocdev")]
+mod gpiocdev;
+#[cfg(feature = "connector-gpiocdev")]
+pub use gpiocdev::{GpioCdev, InputLine, OutputLine};
+
+#[cfg(feature = "connector-network")]
+mod network;
+#[cfg(feature = "connector-network")]
+pub use network::{Listener, Network, Session};
+
 #[cfg(feature = "connector-rpi")]
 mod rpi;
+
+#[cfg(feature = "connector-spi")]
+mod spi;
+#[cfg(feature = "connector-spi")]
+pub use spi::{Spi, SpiBus};
@@ -0,0 +1,569 @@
+//! Network connector and listener.
+//!
+//! Drives a remote chain over TCP using the framed emulator protocol (see `Datagram`),
+//! every datagram prefixed with its length as 32 bit little endian integer.
+//! Clients open with a `Packet::Hello` handshake followed by `Packet::Auth`.
+//!
+//! The shared secret is sent in plain text, use a trusted network or a tunnel.
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{Bits, Connector, Controller, Datagram, Error, Packet, Pin, Pins, PROTOCOL_VERSION};
+
+/// Time to wait for connecting and the handshake.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time to wait before reconnecting after a failed reconnect.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time between checks of the stop signal while serving.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Largest accepted datagram.
+const MAX_DATAGRAM: usize = 1 << 20;
+
+/// Write a length prefixed datagram.
+fn write_datagram(stream: &mut TcpStream, datagram: &Datagram) -> std::io::Result<()> {
+    let bytes = datagram.encode();
+    let len = u32::try_from(bytes.len()).map_err(std::io::Error::other)?;
+
+    let mut message = Vec::with_capacity(4 + bytes.len());
+    message.extend_from_slice(&len.to_le_bytes());
+    message.extend_from_slice(&bytes);
+    stream.write_all(&message)
+}
+
+/// Read a length prefixed datagram, `None` when the stream closed.
+fn read_datagram(stream: &mut TcpStream) -> std::io::Result<Option<Datagram>> {
+    let mut len = [0; 4];
+    match stream.read_exact(&mut len) {
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_DATAGRAM {
+        return Err(std::io::ErrorKind::InvalidData.into());
+    }
+
+    let mut bytes = vec![0; len];
+    stream.read_exact(&mut bytes)?;
+
+    Datagram::decode(&bytes)
+        .map(Some)
+        .ok_or_else(|| std::io::ErrorKind::InvalidData.into())
+}
+
+/// Datagram with a sequence number, without acknowledgement.
+const fn framed(sequence: u32, packet: Packet) -> Datagram {
+    Datagram {
+        sequence: Some(sequence),
+        acknowledge: false,
+        packet,
+    }
+}
+
+/// Network connector, driving a chain attached to a remote `Listener`.
+///
+/// Reconnects when the connection is lost, at most once per second.
+/// Pin changes are sent one per datagram along with their time, see `Packet::TimedPins`.
+/// Wrap the connector in `Batching` or write whole frames to reduce the overhead.
+#[derive(Debug)]
+pub struct Network {
+    /// Listener addresses.
+    addresses: Vec<SocketAddr>,
+
+    /// Shared secret.
+    token: Vec<u8>,
+
+    /// Connection, `None` while disconnected.
+    stream: Option<TcpStream>,
+
+    /// Earliest time to reconnect after a failed reconnect.
+    retry: Option<Instant>,
+
+    /// Sequence number of the last sent datagram.
+    sequence: u32,
+
+    /// Local pin state.
+    state: Pins<bool>,
+
+    /// Epoch of the times of `Packet::TimedPins`.
+    epoch: Instant,
+}
+
+impl Network {
+    /// Connect and authenticate to a `Listener`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when no address could be connected, or with `PermissionDenied`
+    /// when the listener rejects the secret.
+    pub fn connect(address: impl ToSocketAddrs, token: impl AsRef<[u8]>) -> std::io::Result<Self> {
+        let mut network = Self {
+            addresses: address.to_socket_addrs()?.collect(),
+            token: token.as_ref().to_vec(),
+            stream: None,
+            retry: None,
+            sequence: 0,
+            state: Pins::default(),
+            epoch: Instant::now(),
+        };
+        network.stream = Some(network.open()?);
+
+        Ok(network)
+    }
+
+    /// Whether the connector is connected.
+    #[must_use]
+    pub const fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Open and authenticate a connection.
+    fn open(&self) -> std::io::Result<TcpStream> {
+        let mut last = std::io::Error::from(std::io::ErrorKind::AddrNotAvailable);
+
+        for address in &self.addresses {
+            match TcpStream::connect_timeout(address, CONNECT_TIMEOUT) {
+                Ok(stream) => return self.authenticate(stream),
+                Err(error) => last = error,
+            }
+        }
+
+        Err(last)
+    }
+
+    /// Handshake and authenticate on a new connection.
+    fn authenticate(&self, mut stream: TcpStream) -> std::io::Result<TcpStream> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+
+        write_datagram(&mut stream, &framed(0, Packet::Hello(PROTOCOL_VERSION)))?;
+        if !matches!(
+            read_datagram(&mut stream)?,
+            Some(Datagram {
+                packet: Packet::Hello(PROTOCOL_VERSION),
+                ..
+            })
+        ) {
+            return Err(std::io::Error::other("unsupported listener version"));
+        }
+
+        let auth = Datagram {
+            sequence: Some(0),
+            acknowledge: true,
+            packet: Packet::Auth(self.token.clone()),
+        };
+        write_datagram(&mut stream, &auth)?;
+        match read_datagram(&mut stream) {
+            Ok(Some(Datagram {
+                packet: Packet::Ack,
+                ..
+            })) => {}
+            _ => return Err(std::io::ErrorKind::PermissionDenied.into()),
+        }
+
+        stream.set_read_timeout(None)?;
+        Ok(stream)
+    }
+
+    /// Send a packet, reconnecting once when the connection was lost.
+    fn send(&mut self, packet: Packet) -> Result<(), Error> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let datagram = framed(self.sequence, packet);
+
+        if let Some(stream) = &mut self.stream {
+            if write_datagram(stream, &datagram).is_ok() {
+                return Ok(());
+            }
+            self.stream = None;
+        }
+
+        if self.retry.is_some_and(|retry| Instant::now() < retry) {
+            return Err(Error::Disconnected);
+        }
+
+        let Ok(mut stream) = self.open() else {
+            self.retry = Some(Instant::now() + RECONNECT_INTERVAL);
+            return Err(Error::Disconnected);
+        };
+
+        self.retry = None;
+        write_datagram(&mut stream, &datagram)?;
+        self.stream = Some(stream);
+
+        Ok(())
+    }
+}
+
+impl Connector for Network {
+    fn get(&self, pin: Pin) -> bool {
+        self.state.get(pin)
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.try_set_timed(&[(pin, state)], &[Instant::now()])
+    }
+
+    fn try_set_batch(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+        for (pin, state) in changes {
+            self.state.set(*pin, *state);
+        }
+
+        self.send(Packet::Pins(changes.to_vec()))
+    }
+
+    fn try_set_timed(&mut self, changes: &[(Pin, bool)], at: &[Instant]) -> Result<(), Error> {
+        for (pin, state) in changes {
+            self.state.set(*pin, *state);
+        }
+
+        self.send(Packet::TimedPins(
+            changes
+                .iter()
+                .zip(at)
+                .map(|((pin, state), at)| (*pin, *state, at.saturating_duration_since(self.epoch)))
+                .collect(),
+        ))
+    }
+
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        let mut bytes = vec![0; len.div_ceil(8)];
+        for index in (0..len).filter(|index| data.bit(*index)) {
+            bytes[index / 8] |= 1 << (index % 8);
+        }
+
+        if len > 0 {
+            self.state.set(Pin::Clock, true);
+            self.state.set(Pin::Data, data.bit(len - 1));
+        }
+        self.state.set(Pin::Latch, false);
+
+        self.send(Packet::Frame {
+            bits: len,
+            data: bytes,
+        })
+    }
+}
+
+impl Controller<Network> {
+    /// Connect to a TPIC6C596 chain attached to a remote `Listener`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the listener can not be reached or rejects the secret.
+    pub fn network(
+        address: impl ToSocketAddrs,
+        token: impl AsRef<[u8]>,
+        chain: usize,
+    ) -> std::io::Result<Self> {
+        Ok(Self::connect(Network::connect(address, token)?, chain))
+    }
+}
+
+/// Listener accepting `Network` clients, one connection at a time.
+#[derive(Debug)]
+pub struct Listener {
+    /// TCP listener.
+    listener: TcpListener,
+
+    /// Shared secret.
+    token: Vec<u8>,
+}
+
+impl Listener {
+    /// Listen for clients authenticating with `token`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the address can not be bound.
+    pub fn bind(address: impl ToSocketAddrs, token: impl AsRef<[u8]>) -> std::io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            token: token.as_ref().to_vec(),
+        })
+    }
+
+    /// Bound address.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the address can not be read.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept and authenticate the next client.
+    ///
+    /// # Errors
+    ///
+    /// Errors when accepting fails, or with `PermissionDenied`
+    /// when the client fails the handshake or authentication.
+    pub fn accept(&self) -> std::io::Result<Session> {
+        let (mut stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+
+        match read_datagram(&mut stream)? {
+            Some(Datagram {
+                packet: Packet::Hello(_),
+                ..
+            }) => write_datagram(&mut stream, &framed(0, Packet::Hello(PROTOCOL_VERSION)))?,
+            _ => return Err(std::io::ErrorKind::PermissionDenied.into()),
+        }
+
+        match read_datagram(&mut stream)? {
+            Some(Datagram {
+                packet: Packet::Auth(token),
+                ..
+            }) if equals(&token, &self.token) => {
+                write_datagram(&mut stream, &framed(0, Packet::Ack))?;
+            }
+            _ => return Err(std::io::ErrorKind::PermissionDenied.into()),
+        }
+
+        stream.set_read_timeout(None)?;
+        Ok(Session { stream })
+    }
+
+    /// Apply the packets of every client to `controller`, until `stop` is set.
+    ///
+    /// Serves one client at a time, rejected clients and connection errors are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the listener fails.
+    pub fn serve<C: Connector>(
+        &self,
+        controller: &mut Controller<C>,
+        stop: &AtomicBool,
+    ) -> std::io::Result<()> {
+        self.listener.set_nonblocking(true)?;
+
+        while !stop.load(Ordering::Relaxed) {
+            let mut session = match self.accept() {
+                Ok(session) => session,
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            session.stream.set_read_timeout(Some(POLL_INTERVAL))?;
+
+            while !stop.load(Ordering::Relaxed) {
+                match session.receive() {
+                    Ok(Some(packet)) => {
+                        let _ = controller.try_apply(&packet);
+                    }
+                    Err(error)
+                        if matches!(
+                            error.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) => {}
+                    Ok(None) | Err(_) => break,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare secrets without exiting early on the first difference.
+fn equals(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0, |difference, (left, right)| difference | (left ^ right))
+            == 0
+}
+
+/// Authenticated client connection of a `Listener`.
+#[derive(Debug)]
+pub struct Session {
+    /// Client stream.
+    stream: TcpStream,
+}
+
+impl Session {
+    /// Receive the next packet, `None` when the client disconnected.
+    ///
+    /// # Errors
+    ///
+    /// Errors when reading fails or on malformed datagrams.
+    pub fn receive(&mut self) -> std::io::Result<Option<Packet>> {
+        Ok(read_datagram(&mut self.stream)?.map(|datagram| datagram.packet))
+    }
+
+    /// Client address.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the address can not be read.
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl<C: Connector> Controller<C> {
+    /// Apply a received packet to the chain.
+    ///
+    /// Pin changes are set in order, ignoring their times, frames are shifted and latched.
+    /// Other packets are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    pub fn try_apply(&mut self, packet: &Packet) -> Result<(), Error> {
+        match packet {
+            Packet::Pins(changes) => self.try_apply_pins(changes),
+            Packet::TimedPins(changes) => self.try_apply_pins(
+                &changes
+                    .iter()
+                    .map(|(pin, state, _)| (*pin, *state))
+                    .collect::<Vec<_>>(),
+            ),
+            Packet::Frame { bits, data } => self.try_shift(data.as_slice(), *bits),
+            Packet::Hello(_) | Packet::Ack | Packet::Auth(_) => Ok(()),
+        }
+    }
+
+    /// Set received pin changes in order, tracking the control state.
+    fn try_apply_pins(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+        self.connector.try_set_batch(changes)?;
+
+        if let Some((_, state)) = changes.iter().rev().find(|(pin, _)| *pin == Pin::Control) {
+            self.on = *state != self.active_low;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "emulator"))]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::Emulator;
+
+    /// Serve an emulated chain on loopback until stopped.
+    fn serve(
+        chain: usize,
+    ) -> (
+        SocketAddr,
+        Arc<AtomicBool>,
+        std::thread::JoinHandle<Controller<Emulator>>,
+    ) {
+        let listener = Listener::bind("127.0.0.1:0", "secret").expect("bind");
+        let address = listener.local_addr().expect("address");
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let mut controller = Controller::connect(Emulator::new(chain), chain);
+                listener.serve(&mut controller, &stop).expect("serve");
+                controller
+            })
+        };
+
+        (address, stop, thread)
+    }
+
+    /// Stop serving and return the emulated chain.
+    fn stop(
+        stop: &AtomicBool,
+        thread: std::thread::JoinHandle<Controller<Emulator>>,
+    ) -> Controller<Emulator> {
+        // Let the listener drain the connection before stopping.
+        std::thread::sleep(Duration::from_millis(200));
+        stop.store(true, Ordering::Relaxed);
+        thread.join().expect("listener")
+    }
+
+    #[test]
+    fn loopback() {
+        let (address, signal, thread) = serve(2);
+
+        let mut controller = Controller::network(address, "secret", 2).expect("connect");
+        controller.on();
+        controller.write(0x1234_u16);
+        controller.shift_high();
+        drop(controller);
+
+        let mut reference = Controller::connect(Emulator::new(2), 2);
+        reference.on();
+        reference.write(0x1234_u16);
+        reference.shift_high();
+
+        let remote = stop(&signal, thread);
+        assert!(remote.connector().is_on());
+        assert_eq!(
+            remote.connector().registers(),
+            reference.connector().registers()
+        );
+    }
+
+    #[test]
+    fn rejects_secret() {
+        let (address, signal, thread) = serve(1);
+
+        let error = Network::connect(address, "guess").expect_err("rejected");
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+
+        let _ = stop(&signal, thread);
+    }
+
+    #[test]
+    fn reconnects() {
+        let listener = Listener::bind("127.0.0.1:0", "secret").expect("bind");
+        let address = listener.local_addr().expect("address");
+        let remote = std::thread::spawn(move || {
+            let mut controller = Controller::connect(Emulator::new(1), 1);
+            controller.on();
+
+            // Drop the first connection after a frame, keep the second.
+            for _ in 0..2 {
+                let mut session = listener.accept().expect("accept");
+                let packet = session.receive().expect("receive").expect("packet");
+                controller.try_apply(&packet).expect("apply");
+            }
+
+            controller
+        });
+
+        let mut network = Network::connect(address, "secret").expect("connect");
+        network.try_shift_frame(&0xFF_u8, 8).expect("first frame");
+
+        // Writes fail once the listener closed the first connection, and reconnect.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !remote.is_finished() && Instant::now() < deadline {
+            let _ = network.try_shift_frame(&0x0F_u8, 8);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let remote = remote.join().expect("listener");
+        assert!(network.is_connected());
+        assert_eq!(remote.connector().register(0).state(), 0xF0);
+    }
+
+    #[test]
+    fn secrets() {
+        assert!(equals(b"secret", b"secret"));
+        assert!(!equals(b"secret", b"secreT"));
+        assert!(!equals(b"secret", b"secrets"));
+    }
+}
@@ -2,23 +2,42 @@
 
 use rppal::gpio::{Gpio, OutputPin};
 
-use crate::{Connector, Pin, Pins};
+use crate::{Connector, Error, Pin, Pins};
 
 /// Raspberry Pi GPIO connector using `rppal` crate.
+///
+/// `Pin::Clear` is not wired, unwired pins read low.
+/// Requested pins are switched through the GPIO registers directly and never fail,
+/// errors only report unwired pins.
 #[derive(Debug)]
-pub struct RPi(Pins<OutputPin>);
+pub struct RPi(Pins<Option<OutputPin>>);
 
 impl Connector for RPi {
     fn get(&self, pin: Pin) -> bool {
-        self.0.get_ref(pin).is_set_high()
+        self.0
+            .get_ref(pin)
+            .as_ref()
+            .is_some_and(OutputPin::is_set_high)
     }
 
     fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        let output = self
+            .0
+            .get_mut(pin)
+            .as_mut()
+            .ok_or(Error::UnsupportedPin(pin))?;
+
         if state {
-            self.0.get_mut(pin).set_high();
+            output.set_high();
         } else {
-            self.0.get_mut(pin).set_low();
+            output.set_low();
         }
+
+        Ok(())
     }
 }
 
@@ -39,10 +58,11 @@ impl crate::Controller<RPi> {
 
         Ok(Self::connect(
             RPi(Pins {
-                data: gpio.get(data_pin)?.into_output_low(),
-                clock: gpio.get(clock_pin)?.into_output_low(),
-                latch: gpio.get(latch_pin)?.into_output_low(),
-                control: gpio.get(control_pin)?.into_output_low(),
+                clear: None,
+                data: Some(gpio.get(data_pin)?.into_output_low()),
+                clock: Some(gpio.get(clock_pin)?.into_output_low()),
+                latch: Some(gpio.get(latch_pin)?.into_output_low()),
+                control: Some(gpio.get(control_pin)?.into_output_low()),
             }),
             chain,
         ))
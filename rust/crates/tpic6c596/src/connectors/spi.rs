@@ -0,0 +1,279 @@
+//! Linux SPI connector using `spidev` crate.
+//!
+//! MOSI and SCLK of the SPI bus are wired to the TPIC6C596 serial input and
+//! shift register clock, so whole frames are clocked in hardware.
+//! Only the latch and control pins are driven through another connector.
+
+use spidev::{SpiModeFlags, Spidev, SpidevOptions, SpidevTransfer};
+
+use crate::{Bits, Connector, Error, Pin};
+
+/// SPI bus clocking out words on MOSI/SCLK.
+///
+/// Implemented for `spidev::Spidev`,
+/// but can be implemented to inject a mock bus.
+pub trait SpiBus {
+    /// Write `words` to the bus, most significant bit first.
+    ///
+    /// Each word holds `bits` (1 to 8) bits, right aligned.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the transfer fails.
+    fn write(&mut self, words: &[u8], bits: u8) -> Result<(), Error>;
+}
+
+impl SpiBus for Spidev {
+    fn write(&mut self, words: &[u8], bits: u8) -> Result<(), Error> {
+        let mut transfer = SpidevTransfer::write(words);
+        transfer.bits_per_word = bits;
+
+        Ok(self.transfer(&mut transfer)?)
+    }
+}
+
+/// Linux SPI connector using `spidev` crate.
+///
+/// Clocks frames through the SPI bus, while `Pin::Latch`, `Pin::Control`, and `Pin::Clear`
+/// are set using the wrapped `pins` connector (for example GPIO).
+/// `Pin::Data` and `Pin::Clock` can not be set directly.
+#[derive(Debug)]
+pub struct Spi<C: Connector, B: SpiBus = Spidev> {
+    /// SPI bus for data and clock.
+    bus: B,
+
+    /// Connector for latch and control pins.
+    pins: C,
+
+    /// Word buffer, reused between frames.
+    buffer: Vec<u8>,
+}
+
+impl<C: Connector, B: SpiBus> Spi<C, B> {
+    /// Create a SPI connector from a bus and a connector for the latch and control pins.
+    #[must_use]
+    pub const fn new(bus: B, pins: C) -> Self {
+        Self {
+            bus,
+            pins,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// SPI bus.
+    #[must_use]
+    pub const fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Connector for the latch and control pins.
+    #[must_use]
+    pub const fn pins(&self) -> &C {
+        &self.pins
+    }
+}
+
+impl<C: Connector, B: SpiBus> Connector for Spi<C, B> {
+    fn get(&self, pin: Pin) -> bool {
+        match pin {
+            Pin::Latch | Pin::Control | Pin::Clear => self.pins.get(pin),
+            Pin::Data | Pin::Clock => false,
+        }
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        match pin {
+            Pin::Latch | Pin::Control | Pin::Clear => self.pins.try_set(pin, state),
+            Pin::Data | Pin::Clock => Err(Error::UnsupportedPin(pin)),
+        }
+    }
+
+    fn try_get(&self, pin: Pin) -> Result<bool, Error> {
+        match pin {
+            Pin::Latch | Pin::Control | Pin::Clear => self.pins.try_get(pin),
+            Pin::Data | Pin::Clock => Err(Error::UnsupportedPin(pin)),
+        }
+    }
+
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        let whole = len / 8;
+        let rest = len % 8;
+
+        self.buffer.clear();
+        self.buffer
+            .extend((0..whole).map(|word| pack(data, word * 8, 8)));
+
+        if whole > 0 {
+            self.bus.write(&self.buffer, 8)?;
+        }
+
+        if rest > 0 {
+            #[allow(clippy::cast_possible_truncation)]
+            self.bus.write(&[pack(data, whole * 8, rest)], rest as u8)?;
+        }
+
+        crate::latch(self)
+    }
+}
+
+/// Pack `count` (up to 8) bits starting at `start` into a word,
+/// with the first bit as most significant bit.
+fn pack(data: &dyn Bits, start: usize, count: usize) -> u8 {
+    (0..count).fold(0, |word, index| {
+        (word << 1) | u8::from(data.bit(start + index))
+    })
+}
+
+impl<C: Connector> crate::Controller<Spi<C>> {
+    /// Connect to a TPIC6C596 chain using a Linux SPI device.
+    ///
+    /// The SPI device (for example `/dev/spidev0.0`) drives the data and clock pins
+    /// at `speed_hz`, while `pins` drives the latch and control pins.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the SPI device can not be opened or configured.
+    pub fn spidev(
+        device: impl AsRef<std::path::Path>,
+        speed_hz: u32,
+        pins: C,
+        chain: usize,
+    ) -> std::io::Result<Self> {
+        let mut bus = Spidev::open(device)?;
+        bus.configure(
+            &SpidevOptions::new()
+                .bits_per_word(8)
+                .max_speed_hz(speed_hz)
+                .mode(SpiModeFlags::SPI_MODE_0)
+                .build(),
+        )?;
+
+        Ok(Self::connect(Spi::new(bus, pins), chain))
+    }
+}
+
+#[cfg(all(test, feature = "emulator"))]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{Controller, Emulator};
+
+    /// Emulator shared between the mock bus and the pin connector.
+    type Shared = Rc<RefCell<Emulator>>;
+
+    /// Connector setting pins on a shared emulator.
+    #[derive(Debug)]
+    struct SharedPins(Shared);
+
+    impl Connector for SharedPins {
+        fn set(&mut self, pin: Pin, state: bool) {
+            self.0.borrow_mut().set_pin(pin, state);
+        }
+
+        fn get(&self, pin: Pin) -> bool {
+            self.0.borrow().get_pin(pin)
+        }
+    }
+
+    /// Mock SPI bus clocking words into a shared emulator.
+    #[derive(Debug)]
+    struct MockBus {
+        /// Emulator wired to MOSI and SCLK.
+        emulator: Shared,
+
+        /// Number of transfers.
+        transfers: usize,
+    }
+
+    impl SpiBus for MockBus {
+        fn write(&mut self, words: &[u8], bits: u8) -> Result<(), Error> {
+            let mut emulator = self.emulator.borrow_mut();
+
+            for word in words {
+                for bit in (0..bits).rev() {
+                    emulator.set_pin(Pin::Clock, false);
+                    emulator.set_pin(Pin::Data, (word >> bit) & 1 == 1);
+                    emulator.set_pin(Pin::Clock, true);
+                }
+            }
+            emulator.set_pin(Pin::Clock, false);
+
+            self.transfers += 1;
+            Ok(())
+        }
+    }
+
+    /// Create a SPI controller and an emulator controller to compare against.
+    fn controllers(chain: usize) -> (Controller<Spi<SharedPins, MockBus>>, Controller<Emulator>) {
+        let emulator = Shared::new(RefCell::new(Emulator::new(chain)));
+        let bus = MockBus {
+            emulator: Rc::clone(&emulator),
+            transfers: 0,
+        };
+
+        let mut spi = Controller::connect(Spi::new(bus, SharedPins(emulator)), chain);
+        let mut reference = Controller::connect(Emulator::new(chain), chain);
+        spi.on();
+        reference.on();
+
+        (spi, reference)
+    }
+
+    /// Assert both controllers show the same register states.
+    fn assert_same(spi: &Controller<Spi<SharedPins, MockBus>>, reference: &Controller<Emulator>) {
+        let emulator = spi.connector().pins().0.borrow();
+
+        for (index, register) in reference.connector().registers().iter().enumerate() {
+            assert_eq!(emulator.register(index).state(), register.state());
+        }
+    }
+
+    #[test]
+    fn write() {
+        let (mut spi, mut reference) = controllers(12);
+        let frame: Vec<u8> = (0..12).map(|n| 0b1001_0110 ^ n).collect();
+
+        spi.try_write(&frame).expect("write");
+        reference.write(&frame);
+
+        assert_same(&spi, &reference);
+        assert_eq!(spi.connector().bus().transfers, 1);
+    }
+
+    #[test]
+    fn shift_partial_words() {
+        let (mut spi, mut reference) = controllers(3);
+
+        spi.try_shift(0b1_0110_1101_u16, 11).expect("shift");
+        reference.shift(0b1_0110_1101_u16, 11);
+        assert_same(&spi, &reference);
+
+        spi.try_shift_high().expect("shift high");
+        reference.shift_high();
+        spi.try_shift_low().expect("shift low");
+        reference.shift_low();
+        assert_same(&spi, &reference);
+        assert_eq!(spi.connector().bus().transfers, 4);
+    }
+
+    #[test]
+    fn data_and_clock_unsupported() {
+        let (mut spi, _) = controllers(1);
+        let connector = &mut spi.connector;
+
+        assert!(matches!(
+            connector.try_set(Pin::Data, true),
+            Err(Error::UnsupportedPin(Pin::Data))
+        ));
+        assert!(matches!(
+            connector.try_get(Pin::Clock),
+            Err(Error::UnsupportedPin(Pin::Clock))
+        ));
+        assert!(connector.try_set(Pin::Latch, false).is_ok());
+    }
+}
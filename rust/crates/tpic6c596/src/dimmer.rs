@@ -0,0 +1,246 @@
+//! Software brightness using binary code modulation.
+//!
+//! Every refresh cycle shows one frame (bit plane) per intensity bit,
+//! each bit plane is held twice as long as the previous one.
+//! Lights are dimmed by the fraction of the cycle their outputs are on.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{Connector, Controller};
+
+/// Per-light brightness on top of a `Controller`.
+///
+/// Owns the controller and refreshes the chain on a dedicated thread.
+/// Light `n` is bit `n` of the frames written using `Controller::write`.
+#[derive(Debug)]
+pub struct Dimmer<C: Connector + Send + 'static> {
+    /// Intensity per light.
+    levels: Arc<Mutex<Vec<u8>>>,
+
+    /// Intensity bits.
+    depth: u8,
+
+    /// Stop signal for the refresh thread.
+    stop: Arc<AtomicBool>,
+
+    /// Refresh thread, returning the controller when stopped.
+    thread: Option<JoinHandle<Controller<C>>>,
+}
+
+impl<C: Connector + Send + 'static> Dimmer<C> {
+    /// Start dimming the lights of `controller`.
+    ///
+    /// Intensities have `depth` bits (1 to 8).
+    /// The least significant bit plane is shown for `base`,
+    /// so a refresh cycle takes `base * (2^depth - 1)`.
+    /// All lights start off.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `depth` is not between 1 and 8.
+    #[must_use]
+    pub fn start(mut controller: Controller<C>, depth: u8, base: Duration) -> Self {
+        assert!((1..=8).contains(&depth), "depth must be between 1 and 8");
+
+        let levels = Arc::new(Mutex::new(vec![0; controller.lights()]));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let levels = Arc::clone(&levels);
+            let stop = Arc::clone(&stop);
+
+            std::thread::spawn(move || {
+                let mut deadline = Instant::now();
+
+                while !stop.load(Ordering::Relaxed) {
+                    let snapshot = levels
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .clone();
+
+                    for (bit, plane) in planes(&snapshot, depth).iter().enumerate() {
+                        controller.write(plane.as_slice());
+
+                        deadline += base * (1 << bit);
+                        std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    }
+                }
+
+                let max = u8::MAX >> (8 - depth);
+                let full: Vec<bool> = levels
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .iter()
+                    .map(|level| *level >= max)
+                    .collect();
+                controller.write(full.as_slice());
+
+                controller
+            })
+        };
+
+        Self {
+            levels,
+            depth,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Intensity bits.
+    #[must_use]
+    pub const fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Highest intensity, fully on.
+    #[must_use]
+    pub const fn max_level(&self) -> u8 {
+        u8::MAX >> (8 - self.depth)
+    }
+
+    /// Set the intensity of a single light, clamped to `max_level/0`.
+    ///
+    /// Out of range lights are ignored.
+    pub fn set_level(&self, light: usize, level: u8) {
+        let max = self.max_level();
+
+        if let Some(current) = self.lock().get_mut(light) {
+            *current = level.min(max);
+        }
+    }
+
+    /// Set the intensity of all lights, clamped to `max_level/0`.
+    ///
+    /// Lights without a level are turned off.
+    pub fn set_levels(&self, levels: &[u8]) {
+        let max = self.max_level();
+        let mut current = self.lock();
+        let count = current.len();
+
+        current.copy_from_slice(
+            &levels
+                .iter()
+                .map(|level| (*level).min(max))
+                .chain(std::iter::repeat(0))
+                .take(count)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    /// Intensity per light.
+    #[must_use]
+    pub fn levels(&self) -> Vec<u8> {
+        self.lock().clone()
+    }
+
+    /// Stop dimming and return the controller.
+    ///
+    /// Lights at `max_level/0` are left on, all others are turned off.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the refresh thread panicked.
+    #[must_use]
+    pub fn stop(mut self) -> Controller<C> {
+        self.join().expect("dimmer thread running")
+    }
+
+    /// Signal the refresh thread to stop and wait for it.
+    fn join(&mut self) -> Option<Controller<C>> {
+        self.stop.store(true, Ordering::Relaxed);
+
+        self.thread
+            .take()
+            .map(|thread| thread.join().expect("dimmer thread not to panic"))
+    }
+
+    /// Lock the intensities.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        self.levels.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<C: Connector + Send + 'static> Drop for Dimmer<C> {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+/// Split intensities into one frame per bit, least significant bit first.
+fn planes(levels: &[u8], depth: u8) -> Vec<Vec<bool>> {
+    (0..depth)
+        .map(|bit| levels.iter().map(|level| level & (1 << bit) != 0).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_planes() {
+        let planes = planes(&[0, 1, 2, 5, 7], 3);
+
+        assert_eq!(
+            planes,
+            [
+                [false, true, false, true, true],
+                [false, false, true, false, true],
+                [false, false, false, true, true],
+            ]
+        );
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn duty_cycle() {
+        let mut controller = Controller::connect(crate::Emulator::new(1), 1);
+        controller.on();
+
+        let dimmer = Dimmer::start(controller, 3, Duration::from_micros(500));
+        assert_eq!(dimmer.max_level(), 7);
+        dimmer.set_levels(&[7, 0, 1, 2, 3, 4, 5, 9]);
+        dimmer.set_level(1, 6);
+        dimmer.set_level(8, 1);
+        assert_eq!(dimmer.levels(), [7, 6, 1, 2, 3, 4, 5, 7]);
+
+        std::thread::sleep(Duration::from_millis(150));
+        let controller = dimmer.stop();
+
+        // Light `n` ends up in output `7 - n`.
+        let duty = controller.connector().duty_cycles();
+        for (light, level) in [7, 6, 1, 2, 3, 4, 5, 7].into_iter().enumerate() {
+            let expected = f64::from(level) / 7.0;
+            assert!(
+                (duty[7 - light] - expected).abs() < 0.1,
+                "light {light}: {} != {expected}",
+                duty[7 - light]
+            );
+        }
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn layout_lights() {
+        let layout = crate::Layout::new([(0, 0), (0, 7), (1, 0)]).expect("layout");
+        let mut controller = Controller::connect(crate::Emulator::new(2), 2).with_layout(layout);
+        controller.on();
+
+        let dimmer = Dimmer::start(controller, 1, Duration::from_micros(500));
+        dimmer.set_levels(&[1, 0, 1]);
+        assert_eq!(dimmer.levels(), [1, 0, 1]);
+
+        std::thread::sleep(Duration::from_millis(20));
+        let controller = dimmer.stop();
+        assert_eq!(controller.connector().register(0).state(), 0b0000_0001);
+        assert_eq!(controller.connector().register(1).state(), 0b0000_0001);
+    }
+}
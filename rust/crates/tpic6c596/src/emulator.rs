@@ -1,12 +1,17 @@
 //! Emulator for testing
 
-use crate::{Pin, Pins};
+use std::time::{Duration, Instant};
+
+use crate::{
+    timing::{Checker, Timing, Violation},
+    Frame, Pin, Pins,
+};
 
 /// Represents a register in the TPIC6C596 emulator.
 ///
 /// The `Register` struct holds the state of a register, including its buffer,
 /// current state, and whether it is on or off.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Register {
     /// The buffer value of the register.
     buffer: u8,
@@ -49,6 +54,16 @@ impl Register {
         self.state = self.buffer;
     }
 
+    /// Clear the buffer, leaving the state untouched.
+    fn clear(&mut self) {
+        self.buffer = 0;
+    }
+
+    /// The bit that would overflow on the next shift.
+    const fn last_bit(self) -> bool {
+        self.buffer & 0b1000_0000 != 0
+    }
+
     /// Return state.
     ///
     /// Returns `0` if register is off.
@@ -67,6 +82,32 @@ impl Register {
     }
 }
 
+/// Emulated wiring of the TPIC6C596 pins.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Wiring {
+    /// Four pin wiring.
+    ///
+    /// `Pin::Control` high turns the outputs on, `Pin::Latch` commits on its
+    /// falling edge and `Pin::Clear` is ignored (SRCLR tied high).
+    #[default]
+    Simple,
+
+    /// Datasheet pin semantics.
+    ///
+    /// - `Pin::Control` is the active low output enable (G).
+    /// - `Pin::Clear` is the active low shift register clear (SRCLR).
+    /// - `Pin::Clock` (SRCK) shifts on its rising edge.
+    /// - `Pin::Latch` (RCK) commits on its rising edge.
+    Datasheet,
+
+    /// Datasheet pin semantics with RCK tied to SRCK.
+    ///
+    /// Every rising edge of `Pin::Clock` commits the shift register before shifting,
+    /// so the outputs are always one clock behind the shift register.
+    /// `Pin::Latch` is ignored.
+    TiedClocks,
+}
+
 /// Represents an emulator for the TPIC6C596 shift registers.
 ///
 /// The `Emulator` struct provides methods to manipulate and test the behavior of the TPIC6C596 shift register.
@@ -79,6 +120,34 @@ pub struct Emulator {
 
     /// Chain of registers.
     registers: Vec<Register>,
+
+    /// Emulated pin wiring.
+    wiring: Wiring,
+
+    /// Serial output (SER OUT) of the last register in the chain.
+    serial_out: bool,
+
+    /// Timing checks for timestamped pin changes, when enabled.
+    timing: Option<Checker>,
+
+    /// Creation time, used to timestamp untimed pin changes.
+    epoch: Instant,
+
+    /// Output on-time accounting.
+    duty: Duty,
+}
+
+/// Time-averaged output accounting.
+#[derive(Debug)]
+struct Duty {
+    /// Start of the measurement.
+    since: Duration,
+
+    /// Time of the last accounted pin change.
+    last: Duration,
+
+    /// Accumulated on-time per output.
+    on: Vec<Duration>,
 }
 
 impl Emulator {
@@ -93,40 +162,110 @@ impl Emulator {
     /// A new instance of `Emulator`.
     #[must_use]
     pub fn new(chain: usize) -> Self {
+        Self::with_wiring(chain, Wiring::Simple)
+    }
+
+    /// Creates a new emulator with a specific pin wiring.
+    ///
+    /// All pins start low, except `Pin::Clear` which starts high (inactive).
+    /// With datasheet wiring this means the outputs start enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `chain` - The number of registers in the chain.
+    /// * `wiring` - The emulated pin wiring.
+    #[must_use]
+    pub fn with_wiring(chain: usize, wiring: Wiring) -> Self {
+        let mut register = Register::new(0);
+        register.set_on(wiring != Wiring::Simple);
+
         Self {
-            pins: Pins::default(),
-            registers: vec![Register::new(0); chain],
+            pins: Pins {
+                clear: true,
+                ..Pins::default()
+            },
+            registers: vec![register; chain],
+            wiring,
+            serial_out: false,
+            timing: None,
+            epoch: Instant::now(),
+            duty: Duty {
+                since: Duration::ZERO,
+                last: Duration::ZERO,
+                on: vec![Duration::ZERO; chain * 8],
+            },
         }
     }
 
+    /// Enable timing checks for timestamped pin changes.
+    ///
+    /// Pin changes set using `set_pin_at/3` are checked against the `timing` requirements,
+    /// violations are collected and available using `violations/0`.
+    #[must_use]
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = Some(Checker::new(timing));
+        self
+    }
+
+    /// The timing requirements checked, if enabled.
+    #[must_use]
+    pub fn timing(&self) -> Option<Timing> {
+        self.timing.as_ref().map(Checker::timing)
+    }
+
+    /// The emulated pin wiring.
+    #[must_use]
+    pub const fn wiring(&self) -> Wiring {
+        self.wiring
+    }
+
     /// Sets the state of a specified pin.
     ///
+    /// The change is timestamped with the time since the emulator was created.
+    ///
     /// # Arguments
     ///
     /// * `pin` - The pin to set.
     /// * `state` - The state to set the pin to (`true` for high, `false` for low).
     pub fn set_pin(&mut self, pin: Pin, state: bool) {
+        self.change(pin, state, self.epoch.elapsed());
+    }
+
+    /// Apply a pin change at a timestamp.
+    fn change(&mut self, pin: Pin, state: bool, at: Duration) {
         if self.pins.get(pin) != state {
+            self.account(at);
             self.pins.set(pin, state);
 
+            let simple = self.wiring == Wiring::Simple;
+            let tied = self.wiring == Wiring::TiedClocks;
+
             match (pin, state) {
-                (Pin::Latch, false) => {
-                    for register in &mut self.registers {
-                        register.commit();
-                    }
-                }
+                (Pin::Latch, edge) if !tied && edge != simple => self.commit(),
                 (Pin::Clock, true) => {
-                    let mut over = self.pins.data;
+                    if tied {
+                        self.commit();
+                    }
 
-                    for register in &mut self.registers {
-                        over = register.shift(over);
+                    if self.is_clearing() {
+                        self.clear();
+                    } else {
+                        let mut over = self.pins.data;
+
+                        for register in &mut self.registers {
+                            over = register.shift(over);
+                        }
                     }
                 }
-                (Pin::Control, on) => {
+                (Pin::Clock, false) => {
+                    self.serial_out = self.registers.last().is_some_and(|r| r.last_bit());
+                }
+                (Pin::Control, level) => {
                     for register in &mut self.registers {
-                        register.set_on(on);
+                        register.set_on(level == simple);
                     }
                 }
+                (Pin::Clear, false) if !simple => self.clear(),
                 (_, _) => {
                     // Nothing to do.
                 }
@@ -134,6 +273,106 @@ impl Emulator {
         }
     }
 
+    /// Add the time since the last change to the on-time of all enabled outputs.
+    fn account(&mut self, at: Duration) {
+        let elapsed = at.saturating_sub(self.duty.last);
+        self.duty.last = self.duty.last.max(at);
+
+        for (register, outputs) in self.registers.iter().zip(self.duty.on.chunks_mut(8)) {
+            let state = register.state();
+
+            for (bit, on) in outputs.iter_mut().enumerate() {
+                if state & (1 << bit) != 0 {
+                    *on += elapsed;
+                }
+            }
+        }
+    }
+
+    /// Commit all register buffers to their state.
+    fn commit(&mut self) {
+        for register in &mut self.registers {
+            register.commit();
+        }
+    }
+
+    /// Clear all register buffers.
+    fn clear(&mut self) {
+        for register in &mut self.registers {
+            register.clear();
+        }
+    }
+
+    /// Checks if the shift registers are held clear by `Pin::Clear`.
+    const fn is_clearing(&self) -> bool {
+        !matches!(self.wiring, Wiring::Simple) && !self.pins.clear
+    }
+
+    /// Sets the state of a specified pin at a timestamp.
+    ///
+    /// Timestamps are relative to an arbitrary starting point and should not decrease.
+    /// When timing checks are enabled the change is checked for violations.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - The pin to set.
+    /// * `state` - The state to set the pin to (`true` for high, `false` for low).
+    /// * `at` - The time of the change.
+    pub fn set_pin_at(&mut self, pin: Pin, state: bool, at: Duration) {
+        if self.pins.get(pin) != state {
+            if let Some(checker) = self.timing.as_mut() {
+                checker.check(pin, state, at);
+            }
+        }
+
+        self.change(pin, state, at);
+    }
+
+    /// Time-averaged duty cycle per output, from `0.0` (always off) to `1.0` (always on).
+    ///
+    /// Outputs are indexed by register and bit, `register * 8 + bit`,
+    /// where bit `0` is the least significant bit of `Register::state/0`.
+    /// Measured from the creation of the emulator (or `reset_duty_cycles/0`)
+    /// up to the last pin change.
+    #[must_use]
+    pub fn duty_cycles(&self) -> Vec<f64> {
+        let window = self.duty.last.saturating_sub(self.duty.since);
+
+        self.duty
+            .on
+            .iter()
+            .map(|on| {
+                if window.is_zero() {
+                    0.0
+                } else {
+                    on.div_duration_f64(window)
+                }
+            })
+            .collect()
+    }
+
+    /// Restart the duty cycle measurement at the last pin change.
+    pub fn reset_duty_cycles(&mut self) {
+        self.duty.since = self.duty.last;
+        self.duty.on.fill(Duration::ZERO);
+    }
+
+    /// Timing violations collected so far.
+    ///
+    /// Always empty when timing checks are not enabled.
+    #[must_use]
+    pub fn violations(&self) -> &[Violation] {
+        self.timing.as_ref().map_or(&[], Checker::violations)
+    }
+
+    /// Take the timing violations collected so far, clearing the list.
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        self.timing
+            .as_mut()
+            .map(Checker::take_violations)
+            .unwrap_or_default()
+    }
+
     /// Gets the state of a specified pin.
     ///
     /// # Arguments
@@ -144,14 +383,24 @@ impl Emulator {
         self.pins.get(pin)
     }
 
-    /// Checks if the control pin is on.
+    /// Checks if the outputs are on.
     ///
     /// # Returns
     ///
-    /// `true` if the control pin is on, `false` otherwise.
+    /// `true` if the control pin enables the outputs, `false` otherwise.
+    /// With datasheet wiring the control pin (G) is active low.
     #[must_use]
     pub const fn is_on(&self) -> bool {
-        self.pins.control
+        self.pins.control == matches!(self.wiring, Wiring::Simple)
+    }
+
+    /// Serial output (SER OUT) of the chain.
+    ///
+    /// Follows the last bit of the final register,
+    /// updated on the falling edge of `Pin::Clock`.
+    #[must_use]
+    pub const fn serial_out(&self) -> bool {
+        self.serial_out
     }
 
     /// Retrieves a register at a specified index.
@@ -177,6 +426,24 @@ impl Emulator {
     pub fn registers(&self) -> &[Register] {
         &self.registers
     }
+
+    /// The register outputs as the frame written with the default bit and register order.
+    ///
+    /// Writing the returned frame reproduces the outputs, lights of registers that are off read off.
+    #[must_use]
+    pub fn frame(&self) -> Frame {
+        let chain = self.registers.len();
+        let mut frame = Frame::for_chain(chain);
+
+        for (index, register) in self.registers.iter().enumerate() {
+            let byte = chain - 1 - index;
+            for bit in (0..8).filter(|bit| register.state() & (0b1000_0000 >> bit) != 0) {
+                frame.set(byte * 8 + bit, true);
+            }
+        }
+
+        frame
+    }
 }
 
 impl crate::Connector for Emulator {
@@ -189,6 +456,11 @@ impl crate::Connector for Emulator {
     fn set(&mut self, pin: Pin, state: bool) {
         self.set_pin(pin, state);
     }
+
+    #[inline]
+    fn try_serial_out(&mut self) -> Result<bool, crate::Error> {
+        Ok(self.serial_out)
+    }
 }
 
 #[cfg(test)]
@@ -301,4 +573,188 @@ mod tests {
         assert_eq!(emulator.register(1).state(), 0);
         assert_eq!(emulator.register(2).state(), 255);
     }
+
+    /// Shift bits into the emulator and latch using a rising edge on the latch pin.
+    fn write_bits_rising(emulator: &mut Emulator, mut data: u64, bits: u8) {
+        for _ in 0..bits {
+            emulator.set_pin(Pin::Clock, false);
+            emulator.set_pin(Pin::Data, data & 1 != 0);
+            data >>= 1;
+            emulator.set_pin(Pin::Clock, true);
+        }
+        emulator.set_pin(Pin::Clock, false);
+
+        emulator.set_pin(Pin::Latch, true);
+        emulator.set_pin(Pin::Latch, false);
+    }
+
+    #[test]
+    fn simple_ignores_clear() {
+        let mut emulator = Emulator::new(1);
+        emulator.set_pin(Pin::Control, true);
+        emulator.set_pin(Pin::Clear, false);
+
+        write_bits(&mut emulator, 0b1010_0101, 8);
+        assert_eq!(emulator.register(0).state(), 0b1010_0101);
+    }
+
+    #[test]
+    fn datasheet_active_low_output_enable() {
+        let mut emulator = Emulator::with_wiring(2, Wiring::Datasheet);
+        assert!(emulator.is_on());
+
+        write_bits_rising(&mut emulator, 0b1100_0011, 8);
+        assert_eq!(emulator.register(0).state(), 0b1100_0011);
+
+        emulator.set_pin(Pin::Control, true);
+        assert!(!emulator.is_on());
+        assert_eq!(emulator.register(0).state(), 0);
+
+        emulator.set_pin(Pin::Control, false);
+        assert_eq!(emulator.register(0).state(), 0b1100_0011);
+    }
+
+    #[test]
+    fn datasheet_latch_on_rising_edge() {
+        let mut emulator = Emulator::with_wiring(1, Wiring::Datasheet);
+        write_bits_rising(&mut emulator, 0b1111_0000, 8);
+        assert_eq!(emulator.register(0).state(), 0b0000_1111);
+
+        emulator.set_pin(Pin::Data, true);
+        emulator.set_pin(Pin::Clock, true);
+        emulator.set_pin(Pin::Clock, false);
+        assert_eq!(emulator.register(0).state(), 0b0000_1111);
+
+        emulator.set_pin(Pin::Latch, true);
+        assert_eq!(emulator.register(0).state(), 0b0001_1111);
+        emulator.set_pin(Pin::Latch, false);
+        assert_eq!(emulator.register(0).state(), 0b0001_1111);
+    }
+
+    #[test]
+    fn datasheet_clear() {
+        let mut emulator = Emulator::with_wiring(2, Wiring::Datasheet);
+        write_bits_rising(&mut emulator, 0xFFFF, 16);
+
+        // Clearing only affects the shift register, not the outputs.
+        emulator.set_pin(Pin::Clear, false);
+        assert_eq!(emulator.register(0).state(), 0xFF);
+        assert_eq!(emulator.register(1).state(), 0xFF);
+
+        // Shifting while clearing keeps the shift register clear.
+        write_bits_rising(&mut emulator, 0xFF, 8);
+        assert_eq!(emulator.register(0).state(), 0);
+        assert_eq!(emulator.register(1).state(), 0);
+
+        emulator.set_pin(Pin::Clear, true);
+        write_bits_rising(&mut emulator, 0b1, 1);
+        assert_eq!(emulator.register(0).state(), 0b1);
+        assert_eq!(emulator.register(1).state(), 0);
+    }
+
+    #[test]
+    fn tied_clocks_one_behind() {
+        let mut emulator = Emulator::with_wiring(1, Wiring::TiedClocks);
+
+        for _ in 0..3 {
+            emulator.set_pin(Pin::Data, true);
+            emulator.set_pin(Pin::Clock, true);
+            emulator.set_pin(Pin::Clock, false);
+        }
+        assert_eq!(emulator.register(0).state(), 0b011);
+
+        // Latch is ignored.
+        emulator.set_pin(Pin::Latch, true);
+        emulator.set_pin(Pin::Latch, false);
+        assert_eq!(emulator.register(0).state(), 0b011);
+
+        // Extra clock pulse commits the last shifted bit.
+        emulator.set_pin(Pin::Data, false);
+        emulator.set_pin(Pin::Clock, true);
+        assert_eq!(emulator.register(0).state(), 0b111);
+    }
+
+    #[test]
+    fn serial_out_on_falling_edge() {
+        let mut emulator = Emulator::with_wiring(2, Wiring::Datasheet);
+        write_bits_rising(&mut emulator, 0b1, 1);
+
+        for _ in 0..14 {
+            emulator.set_pin(Pin::Data, false);
+            emulator.set_pin(Pin::Clock, true);
+            emulator.set_pin(Pin::Clock, false);
+            assert!(!emulator.serial_out());
+        }
+
+        emulator.set_pin(Pin::Clock, true);
+        assert!(!emulator.serial_out());
+        emulator.set_pin(Pin::Clock, false);
+        assert!(emulator.serial_out());
+    }
+
+    #[test]
+    fn timing_violations() {
+        let mut emulator = Emulator::new(1).with_timing(Timing::DATASHEET);
+        let ns = Duration::from_nanos;
+
+        emulator.set_pin_at(Pin::Data, true, ns(0));
+        emulator.set_pin_at(Pin::Clock, true, ns(50));
+        emulator.set_pin_at(Pin::Clock, false, ns(60));
+        emulator.set_pin_at(Pin::Clock, false, ns(61));
+        assert_eq!(emulator.violations().len(), 1);
+        assert_eq!(
+            emulator.violations()[0].kind,
+            crate::ViolationKind::PulseWidth
+        );
+
+        // Unchanged pins and untimed changes are not checked.
+        emulator.set_pin(Pin::Clock, true);
+        emulator.set_pin_at(Pin::Clock, true, ns(62));
+        assert_eq!(emulator.take_violations().len(), 1);
+        assert!(emulator.violations().is_empty());
+
+        emulator.set_pin(Pin::Control, true);
+        assert_eq!(emulator.register(0).state(), 0);
+        emulator.set_pin(Pin::Latch, true);
+        emulator.set_pin(Pin::Latch, false);
+        assert_eq!(emulator.register(0).state(), 0b11);
+    }
+
+    #[test]
+    fn timing_disabled() {
+        let mut emulator = Emulator::new(1);
+
+        emulator.set_pin_at(Pin::Clock, true, Duration::ZERO);
+        emulator.set_pin_at(Pin::Clock, false, Duration::ZERO);
+        assert!(emulator.timing().is_none());
+        assert!(emulator.violations().is_empty());
+    }
+
+    #[test]
+    fn duty_cycles() {
+        let mut emulator = Emulator::new(1);
+        let ms = Duration::from_millis;
+
+        for (pin, state) in [
+            (Pin::Data, true),
+            (Pin::Clock, true),
+            (Pin::Latch, true),
+            (Pin::Latch, false),
+            (Pin::Control, true),
+        ] {
+            emulator.set_pin_at(pin, state, ms(0));
+        }
+        emulator.set_pin_at(Pin::Control, false, ms(10));
+        emulator.set_pin_at(Pin::Control, true, ms(40));
+
+        let duty = emulator.duty_cycles();
+        assert_eq!(duty.len(), 8);
+        assert!((duty[0] - 0.25).abs() < f64::EPSILON);
+        assert!(duty[1..].iter().all(|duty| duty.abs() < f64::EPSILON));
+
+        emulator.reset_duty_cycles();
+        assert!(emulator.duty_cycles()[0].abs() < f64::EPSILON);
+        emulator.set_pin_at(Pin::Control, false, ms(60));
+        assert!((emulator.duty_cycles()[0] - 1.0).abs() < f64::EPSILON);
+    }
 }
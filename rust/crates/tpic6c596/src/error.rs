@@ -0,0 +1,64 @@
+//! Errors
+
+/// Errors returned by fallible connector and controller operations.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The connector lost its connection to the pins.
+    ///
+    /// For example the emulator or the network listener is no longer running.
+    Disconnected,
+
+    /// IO error while communicating with the pins.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+
+    /// An `embedded-hal` output pin failed to switch.
+    #[cfg(feature = "connector-embedded-hal")]
+    Hal(embedded_hal::digital::ErrorKind),
+
+    /// The connector can not drive this pin directly.
+    ///
+    /// For example the data and clock pins of a SPI connector.
+    UnsupportedPin(crate::Pin),
+
+    /// The connector has no input wired to the serial output (SER OUT).
+    NoSerialOut,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Disconnected => f.write_str("connector disconnected"),
+            #[cfg(feature = "std")]
+            Self::Io(error) => write!(f, "connector io error: {error}"),
+            #[cfg(feature = "connector-embedded-hal")]
+            Self::Hal(kind) => write!(f, "connector pin error: {kind}"),
+            Self::UnsupportedPin(pin) => write!(f, "connector does not support pin {pin:?}"),
+            Self::NoSerialOut => f.write_str("connector has no serial output"),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::NotConnected
+            | std::io::ErrorKind::BrokenPipe => Self::Disconnected,
+            _ => Self::Io(error),
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! Light frames with pattern operations.
+//!
+//! A `Frame` holds one bit per light, light `n` being bit `n` of the frame
+//! written to the chain, in the byte layout of `Bits`.
+
+use std::{
+    ops::{
+        BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Bound, Not, RangeBounds,
+    },
+    str::FromStr,
+};
+
+use crate::Bits;
+
+/// Character of a light that is on.
+const ON: char = '#';
+
+/// Character of a light that is off.
+const OFF: char = '.';
+
+/// Error parsing a `Frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseFrameError {
+    /// Position of the invalid character.
+    pub index: usize,
+
+    /// Invalid character.
+    pub character: char,
+}
+
+impl std::fmt::Display for ParseFrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid light {:?} at {}, expected '{ON}' or '{OFF}'",
+            self.character, self.index
+        )
+    }
+}
+
+impl std::error::Error for ParseFrameError {}
+
+/// Fixed length set of lights, to build patterns like chases, fills, and bounces.
+///
+/// Frames print and parse with one character per light, light `0` first,
+/// `#` for on and `.` for off. Whitespace is ignored when parsing.
+///
+/// ```rust
+/// use tpic6c596::Frame;
+///
+/// let mut frame: Frame = "#.......".parse().unwrap();
+/// frame.rotate_left(3);
+/// assert_eq!(frame.to_string(), "...#....");
+/// assert!(frame.get(3));
+/// ```
+///
+/// Boolean operators keep the length of the left frame,
+/// lights missing from the right frame count as off.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    /// Lights, one bit each, unused bits of the last byte are 0.
+    bytes: Vec<u8>,
+
+    /// Number of lights.
+    len: usize,
+}
+
+impl Frame {
+    /// Frame of `len` lights, all off.
+    #[must_use]
+    pub fn new(len: usize) -> Self {
+        Self {
+            bytes: vec![0; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    /// Frame with a light for every output of a chain of `chain` registers.
+    #[must_use]
+    pub fn for_chain(chain: usize) -> Self {
+        Self::new(chain * 8)
+    }
+
+    /// Frame of the first `len` bits of `data`, for example an integer mask.
+    #[must_use]
+    pub fn from_bits(data: impl Bits, len: usize) -> Self {
+        let mut frame = Self::new(len);
+        for index in (0..len).filter(|index| data.bit(*index)) {
+            frame.set(index, true);
+        }
+
+        frame
+    }
+
+    /// Number of lights.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the frame has no lights.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Lights packed one byte per register, see `Bits`.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Whether `light` is on, `false` past the end.
+    #[must_use]
+    pub fn get(&self, light: usize) -> bool {
+        light < self.len && self.bytes.as_slice().bit(light)
+    }
+
+    /// Turn `light` on or off.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `light` is out of range.
+    pub fn set(&mut self, light: usize, on: bool) {
+        assert!(light < self.len, "light {light} out of range");
+
+        let mask = 1 << (light % 8);
+        if on {
+            self.bytes[light / 8] |= mask;
+        } else {
+            self.bytes[light / 8] &= !mask;
+        }
+    }
+
+    /// Toggle `light`, returning whether it is on now.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `light` is out of range.
+    pub fn toggle(&mut self, light: usize) -> bool {
+        let on = !self.get(light);
+        self.set(light, on);
+
+        on
+    }
+
+    /// Turn the lights in `range` on or off, clamped to the frame.
+    pub fn fill(&mut self, range: impl RangeBounds<usize>, on: bool) {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.len,
+        };
+
+        for light in start..end.min(self.len) {
+            self.set(light, on);
+        }
+    }
+
+    /// Number of lights that are on.
+    #[must_use]
+    pub fn count_on(&self) -> usize {
+        self.bytes
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// Iterate over the lights, whether each is on.
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|light| self.get(light))
+    }
+
+    /// Move every light `count` places up, towards higher lights like `<<` on a mask.
+    ///
+    /// Lights moved past the end are dropped, the first lights turn off.
+    pub fn shift_left(&mut self, count: usize) {
+        self.remap(|light| light.checked_sub(count));
+    }
+
+    /// Move every light `count` places down, towards light `0` like `>>` on a mask.
+    ///
+    /// Lights moved before the start are dropped, the last lights turn off.
+    pub fn shift_right(&mut self, count: usize) {
+        let len = self.len;
+        self.remap(|light| light.checked_add(count).filter(|from| *from < len));
+    }
+
+    /// Move every light `count` places up, wrapping the last lights around to the start.
+    pub fn rotate_left(&mut self, count: usize) {
+        if self.len > 0 {
+            let (len, count) = (self.len, count % self.len);
+            self.remap(|light| Some((light + len - count) % len));
+        }
+    }
+
+    /// Move every light `count` places down, wrapping the first lights around to the end.
+    pub fn rotate_right(&mut self, count: usize) {
+        if self.len > 0 {
+            let (len, count) = (self.len, count % self.len);
+            self.remap(|light| Some((light + count) % len));
+        }
+    }
+
+    /// Turn every light that is on off, and the other way around.
+    pub fn invert(&mut self) {
+        for byte in &mut self.bytes {
+            *byte = !*byte;
+        }
+        self.trim();
+    }
+
+    /// Reverse the order of the lights.
+    pub fn mirror(&mut self) {
+        let last = self.len.saturating_sub(1);
+        self.remap(|light| Some(last - light));
+    }
+
+    /// Set every light to the light `from` returns, off for `None`.
+    fn remap(&mut self, from: impl Fn(usize) -> Option<usize>) {
+        let source = self.clone();
+
+        for light in 0..self.len {
+            self.set(light, from(light).is_some_and(|from| source.get(from)));
+        }
+    }
+
+    /// Clear the unused bits of the last byte.
+    fn trim(&mut self) {
+        if let Some(last) = self.bytes.last_mut() {
+            if self.len % 8 != 0 {
+                *last &= (1 << (self.len % 8)) - 1;
+            }
+        }
+    }
+
+    /// Combine with `other` light by light.
+    fn combine(&mut self, other: &Self, operation: impl Fn(u8, u8) -> u8) {
+        for (index, byte) in self.bytes.iter_mut().enumerate() {
+            *byte = operation(*byte, other.bytes.get(index).copied().unwrap_or(0));
+        }
+        self.trim();
+    }
+}
+
+impl Bits for Frame {
+    #[inline]
+    fn bit(&self, index: usize) -> bool {
+        self.get(index)
+    }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.iter()
+            .try_for_each(|on| std::fmt::Write::write_char(f, if on { ON } else { OFF }))
+    }
+}
+
+impl FromStr for Frame {
+    type Err = ParseFrameError;
+
+    fn from_str(lights: &str) -> Result<Self, Self::Err> {
+        let lights = lights
+            .chars()
+            .enumerate()
+            .filter(|(_, character)| !character.is_whitespace())
+            .map(|(index, character)| match character {
+                ON => Ok(true),
+                OFF => Ok(false),
+                character => Err(ParseFrameError { index, character }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::from_bits(lights.as_slice(), lights.len()))
+    }
+}
+
+impl Not for Frame {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        self.invert();
+        self
+    }
+}
+
+/// Implement a boolean operator and its assigning variant for `Frame`.
+macro_rules! impl_frame_op {
+    ($($op:ident::$method:ident, $assign:ident::$assign_method:ident => $operation:expr),*) => {
+        $(
+            impl $assign<&Self> for Frame {
+                fn $assign_method(&mut self, other: &Self) {
+                    self.combine(other, $operation);
+                }
+            }
+
+            impl $assign for Frame {
+                fn $assign_method(&mut self, other: Self) {
+                    self.combine(&other, $operation);
+                }
+            }
+
+            impl $op<&Self> for Frame {
+                type Output = Self;
+
+                fn $method(mut self, other: &Self) -> Self {
+                    self.combine(other, $operation);
+                    self
+                }
+            }
+
+            impl $op for Frame {
+                type Output = Self;
+
+                fn $method(mut self, other: Self) -> Self {
+                    self.combine(&other, $operation);
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_frame_op!(
+    BitAnd::bitand, BitAndAssign::bitand_assign => |left, right| left & right,
+    BitOr::bitor, BitOrAssign::bitor_assign => |left, right| left | right,
+    BitXor::bitxor, BitXorAssign::bitxor_assign => |left, right| left ^ right
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a frame.
+    fn frame(lights: &str) -> Frame {
+        lights.parse().expect("frame")
+    }
+
+    #[test]
+    fn lights() {
+        let mut lights = Frame::new(10);
+        assert_eq!(lights.to_string(), "..........");
+        assert_eq!(lights.as_bytes(), [0, 0]);
+
+        lights.set(0, true);
+        lights.set(9, true);
+        assert!(lights.toggle(4));
+        assert!(!lights.toggle(0));
+        assert_eq!(lights.to_string(), "....#....#");
+        assert_eq!(lights.count_on(), 2);
+        assert!(!lights.get(10));
+
+        assert_eq!(Frame::for_chain(3).len(), 24);
+        assert_eq!(Frame::from_bits(0b1011_u8, 5), frame("##.#."));
+        assert_eq!(frame(".... #### ##").as_bytes(), [0xF0, 0x03]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "..#x".parse::<Frame>(),
+            Err(ParseFrameError {
+                index: 3,
+                character: 'x'
+            })
+        );
+        assert_eq!("".parse::<Frame>(), Ok(Frame::new(0)));
+    }
+
+    #[test]
+    fn fill() {
+        let mut lights = Frame::new(8);
+        lights.fill(2..5, true);
+        assert_eq!(lights, frame("..###..."));
+
+        lights.fill(4.., true);
+        lights.fill(..=2, false);
+        lights.fill(6..100, false);
+        assert_eq!(lights, frame("...###.."));
+    }
+
+    #[test]
+    fn shift_and_rotate() {
+        let mut lights = frame("##..#...#.");
+
+        lights.shift_left(2);
+        assert_eq!(lights, frame("..##..#..."));
+        lights.shift_right(3);
+        assert_eq!(lights, frame("#..#......"));
+
+        lights.rotate_right(1);
+        assert_eq!(lights, frame("..#......#"));
+        lights.rotate_left(12);
+        assert_eq!(lights, frame(".#..#....."));
+
+        lights.shift_left(100);
+        assert_eq!(lights, Frame::new(10));
+
+        let mut empty = Frame::new(0);
+        empty.rotate_left(3);
+        empty.mirror();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn invert_and_mirror() {
+        let mut lights = frame("##...#.");
+
+        lights.mirror();
+        assert_eq!(lights, frame(".#...##"));
+        lights.invert();
+        assert_eq!(lights, frame("#.###.."));
+        assert_eq!(lights.as_bytes(), [0b0001_1101]);
+        assert_eq!(!lights, frame(".#...##"));
+    }
+
+    #[test]
+    fn boolean_operators() {
+        let left = frame("##..##");
+        let right = frame("#.#.");
+
+        assert_eq!(left.clone() & &right, frame("#....."));
+        assert_eq!(left.clone() | &right, frame("###.##"));
+        assert_eq!(left.clone() ^ right.clone(), frame(".##.##"));
+
+        let mut lights = right;
+        lights |= left;
+        assert_eq!(lights, frame("###."));
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn write() {
+        use crate::{Controller, Emulator};
+
+        let lights = frame("#....... ..##.... .......#");
+        let mut controller = Controller::connect(Emulator::new(3), 3);
+        controller.on();
+        controller.write(&lights);
+
+        assert_eq!(controller.connector().frame(), lights);
+        assert_eq!(controller.connector().register(2).state(), 0b1000_0000);
+    }
+}
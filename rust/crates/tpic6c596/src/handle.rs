@@ -0,0 +1,391 @@
+//! Shared controller access through a command queue.
+//!
+//! A single thread owns the controller and runs commands sent by any number of
+//! cloneable handles, so a server, a watchdog, and manual control can share one chain.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+};
+
+use crate::{Bits, Connector, Controller, Error};
+
+/// Command run by the controller thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Write a frame, see `Controller::write`.
+    Write(Vec<u8>),
+
+    /// Turn the outputs on.
+    On,
+
+    /// Turn the outputs off.
+    Off,
+
+    /// Reset the shift registers to 0.
+    Reset,
+}
+
+/// Command priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// Run after previously queued commands.
+    #[default]
+    Normal,
+
+    /// Run before any queued `Normal` command, for example to turn the outputs off.
+    Safety,
+}
+
+/// Queued command, with a channel for its result when waited on.
+type Job = (Command, Option<mpsc::SyncSender<Result<(), Error>>>);
+
+/// Queued commands.
+#[derive(Debug, Default)]
+struct Queue {
+    /// Commands with `Priority::Safety`.
+    safety: VecDeque<Job>,
+
+    /// Commands with `Priority::Normal`.
+    normal: VecDeque<Job>,
+
+    /// Whether the controller thread is stopping.
+    stopped: bool,
+}
+
+/// State shared between the handles and the controller thread.
+#[derive(Debug)]
+struct Shared {
+    /// Queued commands.
+    queue: Mutex<Queue>,
+
+    /// Signalled when a command is queued or the thread is stopping.
+    queued: Condvar,
+
+    /// Frame length in lights, see `Controller::lights/0`.
+    bits: usize,
+
+    /// Whether the outputs are on, after the last command ran.
+    on: AtomicBool,
+}
+
+/// Cloneable handle sending commands to a `ControllerThread`.
+///
+/// Sending only queues the command and never blocks on the chain,
+/// so handles can be used from async code. See `execute/2` to wait for the result.
+#[derive(Debug, Clone)]
+pub struct ControllerHandle {
+    /// State shared with the controller thread.
+    shared: Arc<Shared>,
+}
+
+impl ControllerHandle {
+    /// Queue a command with `Priority::Normal`.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Disconnected` when the controller thread stopped.
+    pub fn send(&self, command: Command) -> Result<(), Error> {
+        self.send_with_priority(command, Priority::Normal)
+    }
+
+    /// Queue a command.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Disconnected` when the controller thread stopped.
+    pub fn send_with_priority(&self, command: Command, priority: Priority) -> Result<(), Error> {
+        self.enqueue((command, None), priority)
+    }
+
+    /// Queue a command and wait until it ran.
+    ///
+    /// Blocks the calling thread, from async code run it as blocking task.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Disconnected` when the controller thread stopped,
+    /// or when the connector failed to run the command.
+    pub fn execute(&self, command: Command, priority: Priority) -> Result<(), Error> {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        self.enqueue((command, Some(sender)), priority)?;
+
+        receiver.recv().unwrap_or(Err(Error::Disconnected))
+    }
+
+    /// Whether the outputs are on, as of the last command that ran.
+    #[must_use]
+    pub fn is_on(&self) -> bool {
+        self.shared.on.load(Ordering::Relaxed)
+    }
+
+    /// Queue writing a frame, see `Controller::write`.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Disconnected` when the controller thread stopped.
+    pub fn write(&self, data: impl Bits) -> Result<(), Error> {
+        let mut frame = vec![0; self.shared.bits.div_ceil(8)];
+        for index in (0..self.shared.bits).filter(|index| data.bit(*index)) {
+            frame[index / 8] |= 1 << (index % 8);
+        }
+
+        self.send(Command::Write(frame))
+    }
+
+    /// Queue turning the outputs on.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Disconnected` when the controller thread stopped.
+    pub fn on(&self) -> Result<(), Error> {
+        self.send(Command::On)
+    }
+
+    /// Queue turning the outputs off, with `Priority::Safety`.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Disconnected` when the controller thread stopped.
+    pub fn off(&self) -> Result<(), Error> {
+        self.send_with_priority(Command::Off, Priority::Safety)
+    }
+
+    /// Queue resetting the shift registers to 0.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Disconnected` when the controller thread stopped.
+    pub fn reset(&self) -> Result<(), Error> {
+        self.send(Command::Reset)
+    }
+
+    /// Queue a job and wake the controller thread.
+    fn enqueue(&self, job: Job, priority: Priority) -> Result<(), Error> {
+        let mut queue = lock(&self.shared.queue);
+        if queue.stopped {
+            return Err(Error::Disconnected);
+        }
+
+        match priority {
+            Priority::Normal => queue.normal.push_back(job),
+            Priority::Safety => queue.safety.push_back(job),
+        }
+        drop(queue);
+
+        self.shared.queued.notify_one();
+        Ok(())
+    }
+}
+
+/// Thread owning a controller and running the commands of its `ControllerHandle`s.
+///
+/// Commands run in order, `Priority::Safety` commands before the others.
+/// Stops when dropped, see `stop/0` to get the controller back.
+#[derive(Debug)]
+pub struct ControllerThread<C: Connector + Send + 'static> {
+    /// State shared with the handles and the thread.
+    shared: Arc<Shared>,
+
+    /// Controller thread, returning the controller when stopped.
+    thread: Option<JoinHandle<Controller<C>>>,
+}
+
+impl<C: Connector + Send + 'static> ControllerThread<C> {
+    /// Move `controller` to a dedicated thread.
+    #[must_use]
+    pub fn start(mut controller: Controller<C>) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::default(),
+            queued: Condvar::new(),
+            bits: controller.lights(),
+            on: AtomicBool::new(controller.on),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+
+            std::thread::spawn(move || {
+                while let Some((command, reply)) = shared.next() {
+                    let result = match command {
+                        Command::Write(frame) => controller.try_write(frame),
+                        Command::On => controller.try_on(),
+                        Command::Off => controller.try_off(),
+                        Command::Reset => controller.try_reset(),
+                    };
+
+                    shared.on.store(controller.on, Ordering::Relaxed);
+
+                    if let Some(reply) = reply {
+                        let _ = reply.send(result);
+                    }
+                }
+
+                controller
+            })
+        };
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// A new handle sending commands to this thread.
+    #[must_use]
+    pub fn handle(&self) -> ControllerHandle {
+        ControllerHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Run the queued commands, stop, and return the controller.
+    ///
+    /// Handles report `Error::Disconnected` from now on.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the controller thread panicked.
+    #[must_use]
+    pub fn stop(mut self) -> Controller<C> {
+        self.join().expect("controller thread running")
+    }
+
+    /// Signal the controller thread to stop and wait for it.
+    fn join(&mut self) -> Option<Controller<C>> {
+        lock(&self.shared.queue).stopped = true;
+        self.shared.queued.notify_all();
+
+        self.thread
+            .take()
+            .map(|thread| thread.join().expect("controller thread not to panic"))
+    }
+}
+
+impl<C: Connector + Send + 'static> Drop for ControllerThread<C> {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+impl Shared {
+    /// Wait for the next command, `None` when stopped and all commands ran.
+    fn next(&self) -> Option<Job> {
+        let mut queue = self
+            .queued
+            .wait_while(lock(&self.queue), |queue| {
+                !queue.stopped && queue.safety.is_empty() && queue.normal.is_empty()
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let job = queue
+            .safety
+            .pop_front()
+            .or_else(|| queue.normal.pop_front());
+        drop(queue);
+
+        job
+    }
+}
+
+/// Lock a mutex, ignoring poisoning.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(all(test, feature = "emulator"))]
+mod tests {
+    use super::*;
+    use crate::{Emulator, Pin};
+
+    #[test]
+    fn shared() {
+        let thread = ControllerThread::start(Controller::connect(Emulator::new(2), 2));
+        let handle = thread.handle();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let handle = handle.clone();
+                scope.spawn(move || handle.write(0x0FF0_u16).expect("write"));
+            }
+        });
+        handle.on().expect("on");
+        handle
+            .execute(Command::Write(vec![0x81, 0x00]), Priority::Normal)
+            .expect("execute");
+
+        let controller = thread.stop();
+        assert!(controller.connector().is_on());
+        assert_eq!(controller.connector().register(0).state(), 0);
+        assert_eq!(controller.connector().register(1).state(), 0x81);
+
+        assert!(matches!(handle.on(), Err(Error::Disconnected)));
+        assert!(matches!(
+            handle.execute(Command::Off, Priority::Safety),
+            Err(Error::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn layout() {
+        let layout = crate::Layout::new([(1, 0), (0, 7), (0, 0)]).expect("layout");
+        let mut controller = Controller::connect(Emulator::new(2), 2).with_layout(layout);
+        controller.on();
+
+        let thread = ControllerThread::start(controller);
+        thread.handle().write(0b110_u8).expect("write");
+
+        let controller = thread.stop();
+        assert_eq!(controller.connector().register(0).state(), 0b1000_0001);
+        assert_eq!(controller.connector().register(1).state(), 0);
+    }
+
+    #[test]
+    fn safety_first() {
+        /// Connector recording control pin changes and latches.
+        #[derive(Debug, Default)]
+        struct Changes(Vec<(Pin, bool)>);
+
+        impl Connector for Changes {
+            fn set(&mut self, pin: Pin, state: bool) {
+                if matches!(pin, Pin::Control | Pin::Latch) {
+                    self.0.push((pin, state));
+                }
+            }
+
+            fn get(&self, _pin: Pin) -> bool {
+                false
+            }
+        }
+
+        let mut controller = Controller::connect(Changes::default(), 1);
+        controller.on();
+        let thread = ControllerThread::start(controller);
+        let handle = thread.handle();
+
+        // Queue all commands at once, so the order is up to the thread.
+        let mut queue = lock(&thread.shared.queue);
+        queue.normal.push_back((Command::Write(vec![0xFF]), None));
+        queue.normal.push_back((Command::Reset, None));
+        queue.safety.push_back((Command::Off, None));
+        drop(queue);
+        handle.on().expect("on");
+
+        let changes = thread.stop().connector().0.clone();
+        assert_eq!(
+            changes[2..],
+            [
+                (Pin::Control, false),
+                (Pin::Latch, true),
+                (Pin::Latch, false),
+                (Pin::Latch, true),
+                (Pin::Latch, false),
+                (Pin::Control, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Logical light layout.
+//!
+//! Maps logical light indexes to physical register outputs,
+//! for registers mounted upside-down, skipped outputs, or reversed segments.
+
+use crate::Bits;
+
+/// Errors building or loading a `Layout`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LayoutError {
+    /// Output is not between 0 and 7.
+    Output(usize, u8),
+
+    /// Output is mapped to more than one light.
+    Duplicate(usize, u8),
+
+    /// IO error while reading a layout description.
+    Io(std::io::Error),
+
+    /// Invalid layout description.
+    Parse(String),
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Output(register, output) => {
+                write!(f, "register {register} has no output {output}")
+            }
+            Self::Duplicate(register, output) => {
+                write!(f, "output {output} of register {register} is used twice")
+            }
+            Self::Io(error) => write!(f, "layout io error: {error}"),
+            Self::Parse(error) => write!(f, "invalid layout: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Output(..) | Self::Duplicate(..) | Self::Parse(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LayoutError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Mapping of logical lights to register outputs.
+///
+/// Registers are numbered from the controller, register `0` receives the data first.
+/// Outputs `0` to `7` are the register drains, as in `Register::state`.
+/// Outputs without a light stay off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// Register and output per light.
+    lights: Vec<(usize, u8)>,
+}
+
+impl Layout {
+    /// Layout with light `n` on `outputs[n]`, given as (register, output).
+    ///
+    /// # Errors
+    ///
+    /// Errors on outputs above 7 or outputs used by more than one light.
+    pub fn new(outputs: impl IntoIterator<Item = (usize, u8)>) -> Result<Self, LayoutError> {
+        let lights: Vec<(usize, u8)> = outputs.into_iter().collect();
+
+        for (index, &(register, output)) in lights.iter().enumerate() {
+            if output > 7 {
+                return Err(LayoutError::Output(register, output));
+            }
+            if lights[..index].contains(&(register, output)) {
+                return Err(LayoutError::Duplicate(register, output));
+            }
+        }
+
+        Ok(Self { lights })
+    }
+
+    /// Layout of a chain without remapping,
+    /// light `n` is bit `n` of the frame written to the chain.
+    #[must_use]
+    pub fn identity(chain: usize) -> Self {
+        let bits = chain * 8;
+
+        Self {
+            lights: (0..bits)
+                .map(|light| {
+                    let position = bits - 1 - light;
+                    #[allow(clippy::cast_possible_truncation)]
+                    (position / 8, (position % 8) as u8)
+                })
+                .collect(),
+        }
+    }
+
+    /// Number of lights.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    /// Whether the layout has no lights.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// Register and output of a light.
+    #[must_use]
+    pub fn output(&self, light: usize) -> Option<(usize, u8)> {
+        self.lights.get(light).copied()
+    }
+
+    /// Light on a register output, if any.
+    #[must_use]
+    pub fn light(&self, register: usize, output: u8) -> Option<usize> {
+        self.lights
+            .iter()
+            .position(|mapped| *mapped == (register, output))
+    }
+
+    /// Frame to write to a chain of `chain` registers, one bit per output.
+    ///
+    /// Light `n` is bit `n` of `lights`, lights on registers beyond the chain are ignored.
+    /// Frame bit `n` is bit `n % 8` of byte `n / 8`.
+    #[must_use]
+    pub fn frame(&self, lights: &dyn Bits, chain: usize) -> Vec<u8> {
+        let bits = chain * 8;
+        let mut frame = vec![0; chain];
+
+        for (light, &(register, output)) in self.lights.iter().enumerate() {
+            if register < chain && lights.bit(light) {
+                let bit = bits - 1 - (register * 8 + usize::from(output));
+                frame[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+
+        frame
+    }
+
+    /// Light states from register output states, in logical order.
+    ///
+    /// Lights on missing registers are off.
+    #[must_use]
+    pub fn lights(&self, registers: &[u8]) -> Vec<bool> {
+        self.lights
+            .iter()
+            .map(|&(register, output)| {
+                registers
+                    .get(register)
+                    .is_some_and(|state| state & (1 << output) != 0)
+            })
+            .collect()
+    }
+}
+
+/// Layout description.
+#[cfg(feature = "layout")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Description {
+    /// Segments, lights are numbered in segment order.
+    segments: Vec<Segment>,
+}
+
+/// Consecutive lights on a single register.
+#[cfg(feature = "layout")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Segment {
+    /// Register.
+    register: usize,
+
+    /// Used outputs in light order, all outputs when omitted.
+    #[serde(default)]
+    outputs: Option<Vec<u8>>,
+
+    /// Reverse the outputs, for example for registers mounted upside-down.
+    #[serde(default)]
+    reversed: bool,
+}
+
+#[cfg(feature = "layout")]
+impl Layout {
+    /// Parse a TOML layout description.
+    ///
+    /// Lights are described by segments on a single register, numbered in order:
+    ///
+    /// ```toml
+    /// # Lights 0 to 7 on register 1, upside-down.
+    /// [[segments]]
+    /// register = 1
+    /// reversed = true
+    ///
+    /// # Lights 8 to 10 on outputs 0, 2, and 4 of register 0.
+    /// [[segments]]
+    /// register = 0
+    /// outputs = [0, 2, 4]
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors on an invalid description or layout.
+    pub fn from_toml(description: &str) -> Result<Self, LayoutError> {
+        let description: Description =
+            toml::from_str(description).map_err(|error| LayoutError::Parse(error.to_string()))?;
+
+        Self::describe(description)
+    }
+
+    /// Parse a JSON layout description, see `from_toml/1`.
+    ///
+    /// ```json
+    /// { "segments": [{ "register": 1, "reversed": true }, { "register": 0, "outputs": [0, 2, 4] }] }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors on an invalid description or layout.
+    pub fn from_json(description: &str) -> Result<Self, LayoutError> {
+        let description: Description = serde_json::from_str(description)
+            .map_err(|error| LayoutError::Parse(error.to_string()))?;
+
+        Self::describe(description)
+    }
+
+    /// Load a layout description, JSON for `.json` files and TOML otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the file can not be read or on an invalid description or layout.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, LayoutError> {
+        let path = path.as_ref();
+        let description = std::fs::read_to_string(path)?;
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::from_json(&description)
+        } else {
+            Self::from_toml(&description)
+        }
+    }
+
+    /// Build a layout from a description.
+    fn describe(description: Description) -> Result<Self, LayoutError> {
+        Self::new(description.segments.into_iter().flat_map(|segment| {
+            let mut outputs = segment.outputs.unwrap_or_else(|| (0..8).collect());
+            if segment.reversed {
+                outputs.reverse();
+            }
+
+            outputs
+                .into_iter()
+                .map(move |output| (segment.register, output))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity() {
+        let layout = Layout::identity(2);
+
+        assert_eq!(layout.len(), 16);
+        assert_eq!(layout.output(0), Some((1, 7)));
+        assert_eq!(layout.output(15), Some((0, 0)));
+        assert_eq!(layout.light(1, 6), Some(1));
+        assert_eq!(layout.frame(&0xA5C3_u16, 2), [0xC3, 0xA5]);
+        assert_eq!(
+            layout.lights(&[0b0000_0001, 0b1000_0000]),
+            (0..16)
+                .map(|light| light == 0 || light == 15)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn remapped() {
+        let layout = Layout::new([(0, 0), (0, 1), (2, 3)]).expect("layout");
+
+        assert_eq!(layout.frame(&0b111_u8, 1), [0b1100_0000]);
+        assert_eq!(layout.frame(&0b100_u8, 3), [0b0001_0000, 0, 0]);
+        assert_eq!(layout.lights(&[0b10, 0, 0b1000]), [false, true, true]);
+        assert_eq!(layout.lights(&[0b11]), [true, true, false]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Layout::new([(0, 8)]),
+            Err(LayoutError::Output(0, 8))
+        ));
+        assert!(matches!(
+            Layout::new([(1, 2), (0, 2), (1, 2)]),
+            Err(LayoutError::Duplicate(1, 2))
+        ));
+    }
+
+    #[cfg(feature = "layout")]
+    #[test]
+    fn descriptions() {
+        let expected = Layout::new([
+            (1, 7),
+            (1, 6),
+            (1, 5),
+            (1, 4),
+            (1, 3),
+            (1, 2),
+            (1, 1),
+            (1, 0),
+            (0, 0),
+            (0, 2),
+            (0, 4),
+        ])
+        .expect("layout");
+
+        let toml = "[[segments]]\nregister = 1\nreversed = true\n\n[[segments]]\nregister = 0\noutputs = [0, 2, 4]\n";
+        assert_eq!(Layout::from_toml(toml).expect("toml"), expected);
+
+        let json = r#"{ "segments": [{ "register": 1, "reversed": true }, { "register": 0, "outputs": [0, 2, 4] }] }"#;
+        assert_eq!(Layout::from_json(json).expect("json"), expected);
+
+        assert!(matches!(
+            Layout::from_toml("[[segments]]\nregister = 0\noutputs = [9]\n"),
+            Err(LayoutError::Output(0, 9))
+        ));
+        assert!(matches!(
+            Layout::from_json(r#"{ "segments": [{ "output": 1 }] }"#),
+            Err(LayoutError::Parse(_))
+        ));
+    }
+}
@@ -7,15 +7,36 @@
 //!
 //! # Features
 //!
+//! - `std` (default): Enables everything requiring the standard library.
+//!   Without it the crate is `no_std`, keeping `Controller`, `Connector`, and `Pins`.
 //! - `emulator`: Enables an emulator for testing purposes. When this feature is enabled,
 //!   the `Emulator` and `Register` types are available for use.
+//!   The emulator can check timestamped pin changes against the datasheet `Timing`.
+//! - `recording`: Adds the `Recording` connector, wrapping any connector and writing
+//!   every pin change to a Value Change Dump (VCD) file for waveform viewers.
+//!   When wrapped around the `Emulator`, register outputs are recorded too.
+//!   Recorded traces (VCD or a compact binary log) can be played back into any
+//!   connector using `Replay`, with original or scaled timing.
+//! - `layout`: Adds loading a `Layout` from a TOML or JSON description.
 //! - `delay`: Adds a small delay after latching to ensure the TPIC6C596 properly detects
 //!   the latch. This feature is useful for certain hardware configurations that require
-//!   a delay to function correctly.
+//!   a delay to function correctly. See `Controller::with_latch_pulse` to set it at runtime.
+//! - `connector-embedded-hal`: Adds a build in connector for any four `embedded-hal`
+//!   output pins, also under `no_std`. Useable using `Controller::embedded_hal`.
 //! - `connector-emulator`: Adds a build in connector for the emulator. Useable
-//!    using `Connector::emulator` or `Connector::emulator_on_socket`.
+//!   using `Connector::emulator` or `Connector::emulator_on_socket`.
+//!   `Controller::emulator_v2_on_socket` uses the framed protocol (see `Datagram`),
+//!   sending whole frames in a single datagram with optional acknowledgements.
+//! - `connector-gpiocdev`: Adds a build in connector for Linux GPIO character devices
+//!   (`/dev/gpiochipN`). Useable using `Controller::gpio_cdev`.
+//! - `connector-network`: Adds a build in connector driving a remote chain over TCP,
+//!   served by a `Listener` with a shared secret. Useable using `Controller::network`.
+//!   The secret is not encrypted, use a trusted network or a tunnel.
 //! - `connector-rpi`: Adds a build in connector for the Raspberry Pi GPIO.
-//!    Useable using `Connector::rpi_gpio`.
+//!   Useable using `Connector::rpi_gpio`.
+//! - `connector-spi`: Adds a build in connector clocking frames through a Linux SPI device
+//!   (`/dev/spidevN.M`), with latch and control on another connector.
+//!   Useable using `Controller::spidev`.
 //!
 //! # Example
 //!
@@ -42,39 +63,303 @@
 //! controller.off();
 //! ```
 //!
+//! # Frames
+//!
+//! Any type implementing `Bits` can be written to the chain.
+//! Integers cover chains up to their bit width, while byte slices
+//! (one byte per register) cover chains of any length.
+//!
+//! ```rust
+//! # use tpic6c596::{Controller, Connector, Pin};
+//! # struct MyConnector;
+//! # impl Connector for MyConnector {
+//! #     fn set(&mut self, pin: Pin, state: bool) {}
+//! #     fn get(&self, pin: Pin) -> bool { false }
+//! # }
+//! let mut controller = Controller::connect(MyConnector, 12);
+//! controller.write([0b1010_1010; 12]);
+//! ```
+//!
+//! `Frame` holds the lights of a chain with pattern operations like rotating,
+//! filling, and mirroring, and prints and parses as `"..##..#"`.
+//!
+//! The controller tracks the last written lights in `Controller::state`.
+//! Single lights can be changed with `Controller::set_light` and `Controller::toggle`,
+//! and `Controller::commit` writes them only when they changed.
+//!
+//! # Bit and register order
+//!
+//! By default frame byte `0` is shifted first, least significant bit first,
+//! ending up bit reversed on the register furthest from the controller.
+//! Boards wired differently can use `Controller::with_bit_order` (see `BitOrder`)
+//! and `Controller::with_register_order` (see `RegisterOrder`) instead.
+//!
+//! # Layout
+//!
+//! The order of the outputs on the wire rarely matches the physical light order.
+//! A `Layout` maps logical lights to register outputs and is applied to every frame
+//! written by a controller using `Controller::with_layout`.
+//!
+//! # Batching
+//!
+//! Connectors with a per change overhead, like the emulator socket, can be wrapped in
+//! `Batching` (or use `Controller::batched`). Clock and data changes are then sent
+//! in a single `Connector::try_set_batch` call at the end of every latch pulse.
+//!
+//! # Mirroring
+//!
+//! A `Tee` forwards every pin change to several connectors, for example to show
+//! the same frames on the real chain and the emulator (see `Controller::mirrored`).
+//! Every target has its own `Failure` behaviour, so a failing mirror can be ignored
+//! or detached without affecting the chain.
+//!
+//! # Parallel chains
+//!
+//! A `MultiController` drives several chains sharing the clock, latch, and control pins,
+//! each with its own data pin (see `MultiConnector`). All chains shift on the same clock
+//! edges, so writing takes as many clock cycles as the longest chain.
+//! It shares the settings of a `Controller`, taking the chains as a single chain of all
+//! registers for the layout.
+//! The `MultiEmulator` emulates parallel chains, `MultiController::gpio_cdev` drives them
+//! using Linux GPIO with a data line per chain (`GpioCdev::with_data_line`).
+//!
+//! # Brightness
+//!
+//! A `Dimmer` takes over a controller and refreshes the chain on a dedicated thread,
+//! dimming every light individually using binary code modulation.
+//!
+//! # Clock rate
+//!
+//! By default bits are shifted as fast as the connector allows.
+//! `Controller::with_clock_frequency` and `Controller::with_latch_pulse` pace the shift clock
+//! and latch at runtime, for example for long cable runs, and the achieved rate
+//! is available as `ClockStats`.
+//!
+//! # Frame rate
+//!
+//! A `FrameScheduler` takes over a controller and refreshes the chain at a fixed rate
+//! on a dedicated thread. Producers submit frames into a back buffer at any time,
+//! and can pace themselves using `FrameScheduler::present`.
+//! Dropped frames and refresh jitter are available as `FrameStats`.
+//!
+//! # Shared access
+//!
+//! A `ControllerThread` owns a controller on a dedicated thread and runs the `Command`s
+//! sent by cloneable `ControllerHandle`s, `Priority::Safety` commands first.
+//! Sending never blocks on the chain, so handles can be shared with async code.
+//!
+//! # Watchdog
+//!
+//! A `Watchdog` puts the chain in a safe state when no frame is written within a deadline,
+//! turning the outputs off or writing a safe frame (see `Fallback`), and reports
+//! a `WatchdogEvent` to an optional hook. `Watchdog::start` checks on a dedicated thread.
+//! It supervises a `Controller`, or a `ControllerHandle` to share the chain with other
+//! producers, which feed the watchdog using `Watchdog::feed` (see `Supervised`).
+//!
+//! # Chain detection
+//!
+//! With the serial output (SER OUT) of the last register wired to an input,
+//! `Controller::detect_chain` measures the chain length by shifting a probe through it.
+//! Connectors expose the input using `Connector::try_serial_out`,
+//! the `Emulator` models it.
+//!
+//! # Connector selection
+//!
+//! Tools choosing the connector at runtime use `AnyConnector`, opened from a description
+//! like `emulator:/tmp/tpic6c596-emulator.sock` or `rpi:17,22,27,12` (see `ConnectorSpec`).
+//! `Controller::from_env` reads the description from the `TPIC6C596_CONNECTOR` environment variable.
+//!
+//! # `no_std`
+//!
+//! Without the default `std` feature the crate builds under `no_std`,
+//! for example to drive a chain from a microcontroller using `EmbeddedHal`.
+//! Types built on threads or allocation, like `Dimmer` and `Layout`, require `std`.
+//!
+//! # Errors
+//!
+//! Connectors can fail, for example when the emulator is no longer running.
+//! Every `Controller` operation has a fallible `try_` variant returning an `Error`,
+//! like `try_write` and `try_shift`. The infallible variants ignore connector errors.
+//!
 //! # Testing
 //!
 //! When the `emulator` feature is enabled, the crate includes tests that use the `Emulator`
 //! to verify the functionality of the `Controller`. These tests ensure that the controller
 //! correctly shifts bits, turns the registers on and off, and resets the registers.
+//! The `EmbeddedHal` connector is tested on the host against `embedded-hal-mock` pins.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "std")]
+mod any;
+
+#[cfg(feature = "std")]
+pub use any::{AnyConnector, ConnectorSpec, SpecError, CONNECTOR_ENV, EMULATOR_SOCKET};
+
+#[cfg(feature = "std")]
+mod batching;
+
+#[cfg(feature = "std")]
+pub use batching::Batching;
+
+mod bits;
+pub use bits::Bits;
+
+#[cfg(feature = "std")]
+mod clock;
+
+#[cfg(feature = "std")]
+pub use clock::ClockStats;
+
+#[cfg(feature = "std")]
+mod dimmer;
+
+#[cfg(feature = "std")]
+pub use dimmer::Dimmer;
+
+mod error;
+pub use error::Error;
+
+#[cfg(feature = "std")]
+mod frame;
+
+#[cfg(feature = "std")]
+pub use frame::{Frame, ParseFrameError};
+
+#[cfg(feature = "std")]
+mod handle;
+
+#[cfg(feature = "std")]
+pub use handle::{Command, ControllerHandle, ControllerThread, Priority};
 
 #[cfg(feature = "emulator")]
 mod emulator;
 
 #[cfg(feature = "emulator")]
-pub use emulator::{Emulator, Register};
+pub use emulator::{Emulator, Register, Wiring};
+
+#[cfg(feature = "std")]
+mod layout;
+
+#[cfg(feature = "std")]
+pub use layout::{Layout, LayoutError};
+
+#[cfg(feature = "std")]
+mod multi;
+
+#[cfg(feature = "std")]
+pub use multi::{MultiConnector, MultiController};
+
+#[cfg(feature = "emulator")]
+pub use multi::MultiEmulator;
+
+mod order;
+pub use order::{BitOrder, RegisterOrder};
+
+#[cfg(feature = "std")]
+mod scheduler;
+
+#[cfg(feature = "std")]
+pub use scheduler::{FrameScheduler, FrameStats};
+
+#[cfg(feature = "std")]
+mod tee;
+
+#[cfg(feature = "std")]
+pub use tee::{Failure, Tee};
+
+#[cfg(feature = "std")]
+mod tracked;
+
+#[cfg(feature = "std")]
+mod watchdog;
+
+#[cfg(feature = "std")]
+pub use watchdog::{
+    Fallback, Monotonic, Supervised, TimeSource, Watchdog, WatchdogEvent, WatchdogThread,
+};
+
+#[cfg(feature = "emulator")]
+mod timing;
+
+#[cfg(feature = "emulator")]
+pub use timing::{Timing, Violation, ViolationKind};
+
+#[cfg(feature = "recording")]
+mod recording;
 
-#[cfg(any(feature = "connector-emulator", feature = "connector-rpi"))]
+#[cfg(feature = "recording")]
+pub use recording::{Recording, Vcd};
+
+#[cfg(feature = "recording")]
+mod replay;
+
+#[cfg(feature = "recording")]
+pub use replay::{Change, InvalidScale, Pacing, Replay, Scale, Trace};
+
+#[cfg(any(
+    feature = "connector-emulator",
+    feature = "connector-network",
+    feature = "emulator"
+))]
+mod protocol;
+
+#[cfg(any(
+    feature = "connector-emulator",
+    feature = "connector-network",
+    feature = "emulator"
+))]
+pub use protocol::{Datagram, Packet, PROTOCOL_VERSION};
+
+#[cfg(any(
+    feature = "connector-embedded-hal",
+    feature = "connector-emulator",
+    feature = "connector-gpiocdev",
+    feature = "connector-network",
+    feature = "connector-rpi",
+    feature = "connector-spi"
+))]
 mod connectors;
 
+#[cfg(feature = "connector-embedded-hal")]
+pub use connectors::EmbeddedHal;
+
+#[cfg(feature = "connector-gpiocdev")]
+pub use connectors::{GpioCdev, InputLine, OutputLine};
+
+#[cfg(feature = "connector-network")]
+pub use connectors::{Listener, Network, Session};
+
+#[cfg(feature = "connector-spi")]
+pub use connectors::{Spi, SpiBus};
+
 /// Represents the pins of the TPIC6C596 shift register.
-#[derive(Debug, Clone, Copy)]
+///
+/// The basic four pin wiring uses `Clock`, `Control`, `Data`, and `Latch`.
+/// `Clear` is optional, connectors without it report `Error::UnsupportedPin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Pin {
-    /// Clock pin.
+    /// Clear pin (SRCLR).
+    ///
+    /// Active low, clears the shift register while low.
+    Clear,
+    /// Clock pin (SRCK).
     Clock,
-    /// Control pin.
+    /// Control pin (G).
     Control,
-    /// Data pin.
+    /// Data pin (SER IN).
     Data,
-    /// Latch pin.
+    /// Latch pin (RCK).
     Latch,
 }
 
 /// Represents a set of pins in the TPIC6C596 emulator.
 ///
-/// The `Pins` struct holds a state for the clock, control, data, and latch pins.
+/// The `Pins` struct holds a state for the clear, clock, control, data, and latch pins.
 #[derive(Debug, Default)]
 pub struct Pins<T> {
+    /// Clear pin.
+    clear: T,
     /// Clock pin.
     clock: T,
     /// Control pin.
@@ -92,6 +377,7 @@ impl<T> Pins<T> {
     #[must_use]
     pub const fn get_ref(&self, pin: Pin) -> &T {
         match pin {
+            Pin::Clear => &self.clear,
             Pin::Clock => &self.clock,
             Pin::Control => &self.control,
             Pin::Data => &self.data,
@@ -105,6 +391,7 @@ impl<T> Pins<T> {
     #[must_use]
     pub fn get_mut(&mut self, pin: Pin) -> &mut T {
         match pin {
+            Pin::Clear => &mut self.clear,
             Pin::Clock => &mut self.clock,
             Pin::Control => &mut self.control,
             Pin::Data => &mut self.data,
@@ -115,6 +402,7 @@ impl<T> Pins<T> {
     /// Set a pin value.
     pub fn set(&mut self, pin: Pin, value: T) {
         match pin {
+            Pin::Clear => self.clear = value,
             Pin::Clock => self.clock = value,
             Pin::Control => self.control = value,
             Pin::Data => self.data = value,
@@ -128,6 +416,7 @@ impl<T: Copy> Pins<T> {
     #[must_use]
     pub const fn get(&self, pin: Pin) -> T {
         match pin {
+            Pin::Clear => self.clear,
             Pin::Clock => self.clock,
             Pin::Control => self.control,
             Pin::Data => self.data,
@@ -137,6 +426,9 @@ impl<T: Copy> Pins<T> {
 }
 
 /// Connector between the controller and IO pins.
+///
+/// Connectors that can fail should implement `try_set` and `try_get`
+/// to report errors, the default implementations never fail.
 pub trait Connector {
     /// Set a pin's state.
     fn set(&mut self, pin: Pin, state: bool);
@@ -144,6 +436,84 @@ pub trait Connector {
     /// Get a pin's state.
     #[must_use]
     fn get(&self, pin: Pin) -> bool;
+
+    /// Try to set a pin's state.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the pin state could not be set.
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.set(pin, state);
+        Ok(())
+    }
+
+    /// Try to get a pin's state.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the pin state could not be read.
+    fn try_get(&self, pin: Pin) -> Result<bool, Error> {
+        Ok(self.get(pin))
+    }
+
+    /// Try to set several pin states, in order.
+    ///
+    /// The default implementation sets every pin using `try_set`.
+    /// Connectors with a per call overhead, like a socket, can send the changes at once.
+    ///
+    /// # Errors
+    ///
+    /// Errors when a pin state could not be set.
+    /// Setting stops at the first error.
+    fn try_set_batch(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+        changes
+            .iter()
+            .try_for_each(|(pin, state)| self.try_set(*pin, *state))
+    }
+
+    /// Try to set several pin states, in order, `at` holding the time of every change.
+    ///
+    /// The default implementation ignores the times and uses `try_set_batch`.
+    /// Connectors forwarding the changes, like the emulator socket,
+    /// can send the times along so the timing can be checked.
+    ///
+    /// # Errors
+    ///
+    /// Errors when a pin state could not be set.
+    #[cfg(feature = "std")]
+    fn try_set_timed(
+        &mut self,
+        changes: &[(Pin, bool)],
+        at: &[std::time::Instant],
+    ) -> Result<(), Error> {
+        let _ = at;
+        self.try_set_batch(changes)
+    }
+
+    /// Shift the first `len` bits of `data` into the registers and latch them.
+    ///
+    /// The default implementation clocks every bit through `Pin::Data` and `Pin::Clock`.
+    /// Connectors able to clock whole frames in hardware can override this.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    /// Shifting stops at the first error.
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        clock_frame(self, data, len)
+    }
+
+    /// Try to read the serial output (SER OUT) of the last register in the chain.
+    ///
+    /// The serial output is an optional input, used by `Controller::try_detect_chain`.
+    /// The default implementation reports `Error::NoSerialOut`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the serial output is not wired or could not be read.
+    fn try_serial_out(&mut self) -> Result<bool, Error> {
+        Err(Error::NoSerialOut)
+    }
 }
 
 /// A controller to manage a TPIC6C596 register chain.
@@ -158,6 +528,28 @@ pub struct Controller<C: Connector> {
     /// Bits to write to chain.
     bits: usize,
 
+    /// Whether `Pin::Control` is active low,
+    /// as when wired directly to the TPIC6C596 output enable (G).
+    active_low: bool,
+
+    /// Bit order of written frames.
+    bit_order: BitOrder,
+
+    /// Register order of written frames.
+    register_order: RegisterOrder,
+
+    /// Logical light layout applied to written frames.
+    #[cfg(feature = "std")]
+    layout: Option<Layout>,
+
+    /// Shift clock timing and statistics.
+    #[cfg(feature = "std")]
+    clocking: clock::Clocking,
+
+    /// Written and pending lights.
+    #[cfg(feature = "std")]
+    tracking: tracked::Tracking,
+
     // Local State
     /// On/off state of the TPIC6C596 registers.
     on: bool,
@@ -171,60 +563,297 @@ impl<C: Connector> Controller<C> {
     }
 
     /// Connect controller to TPIC6C596 shift registers.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to reset the latch or read the control pin.
+    pub fn try_connect(mut connector: C, chain: usize) -> Result<Self, Error> {
+        connector.try_set(Pin::Latch, false)?;
+        let on = connector.try_get(Pin::Control)?;
+
+        Ok(Self::new(connector, chain, on))
+    }
+
+    /// Connect controller to TPIC6C596 shift registers.
+    ///
+    /// Connector errors are ignored, see `try_connect/2`.
     #[must_use]
     pub fn connect(mut connector: C, chain: usize) -> Self {
         connector.set(Pin::Latch, false);
+        let on = connector.get(Pin::Control);
 
+        Self::new(connector, chain, on)
+    }
+
+    /// Controller with the default settings, see `connect/2`.
+    #[allow(clippy::missing_const_for_fn)] // Only const without std.
+    fn new(connector: C, chain: usize, on: bool) -> Self {
         Self {
-            on: connector.get(Pin::Control),
             connector,
-            bits: chain * 8,
             chain,
+            bits: chain * 8,
+            active_low: false,
+            bit_order: BitOrder::LsbFirst,
+            register_order: RegisterOrder::Forward,
+            #[cfg(feature = "std")]
+            layout: None,
+            #[cfg(feature = "std")]
+            clocking: clock::Clocking::default(),
+            #[cfg(feature = "std")]
+            tracking: tracked::Tracking::new(chain * 8),
+            on,
+        }
+    }
+
+    /// Replace the connector, keeping the settings and state.
+    #[cfg(feature = "std")]
+    fn map_connector<D: Connector>(self, map: impl FnOnce(C) -> D) -> Controller<D> {
+        Controller {
+            connector: map(self.connector),
+            chain: self.chain,
+            bits: self.bits,
+            active_low: self.active_low,
+            bit_order: self.bit_order,
+            register_order: self.register_order,
+            layout: self.layout,
+            clocking: self.clocking,
+            tracking: self.tracking,
+            on: self.on,
         }
     }
 
+    /// Treat `Pin::Control` as active low.
+    ///
+    /// Use when the control pin is wired directly to the TPIC6C596 output enable (G),
+    /// which turns the outputs on while low.
+    #[must_use]
+    pub const fn with_active_low_control(mut self) -> Self {
+        if !self.active_low {
+            self.active_low = true;
+            self.on = !self.on;
+        }
+
+        self
+    }
+
+    /// Shift the bits of every frame byte in `order`, see `BitOrder`.
+    ///
+    /// Applies to frames written using `write/1` without a layout,
+    /// `shift/2` still shifts raw bits.
+    #[must_use]
+    pub const fn with_bit_order(mut self, order: BitOrder) -> Self {
+        self.bit_order = order;
+        self
+    }
+
+    /// Assign frame bytes to registers in `order`, see `RegisterOrder`.
+    ///
+    /// Applies to frames written using `write/1` without a layout,
+    /// `shift/2` still shifts raw bits.
+    #[must_use]
+    pub const fn with_register_order(mut self, order: RegisterOrder) -> Self {
+        self.register_order = order;
+        self
+    }
+
+    /// Bit order of written frames.
+    #[must_use]
+    pub const fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    /// Register order of written frames.
+    #[must_use]
+    pub const fn register_order(&self) -> RegisterOrder {
+        self.register_order
+    }
+
+    /// Map logical lights to register outputs, see `Layout`.
+    ///
+    /// Frames written using `write/1` are in logical light order,
+    /// the bit and register order are ignored, and `shift/2` still shifts raw bits.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.tracking = tracked::Tracking::new(layout.len());
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Logical light layout, if any.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub const fn layout(&self) -> Option<&Layout> {
+        self.layout.as_ref()
+    }
+
     /// The length of the register chain.
     #[must_use]
     pub const fn register_chain(&self) -> usize {
         self.chain
     }
 
+    /// Number of lights in the frames written using `write/1`.
+    ///
+    /// The number of layout lights with a layout, all register outputs otherwise.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)] // Only const without a layout.
+    pub fn lights(&self) -> usize {
+        #[cfg(feature = "std")]
+        if let Some(layout) = &self.layout {
+            return layout.len();
+        }
+
+        self.bits
+    }
+
     /// Turn shift registers on.
+    ///
+    /// Connector errors are ignored, see `try_on/0`.
     pub fn on(&mut self) {
+        let _ = self.try_on();
+    }
+
+    /// Try to turn shift registers on.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set the control pin.
+    pub fn try_on(&mut self) -> Result<(), Error> {
         if !self.on {
-            self.connector.set(Pin::Control, true);
+            self.connector.try_set(Pin::Control, !self.active_low)?;
             self.on = true;
         }
+
+        Ok(())
     }
 
     /// Turn shift registers off.
+    ///
+    /// Connector errors are ignored, see `try_off/0`.
     pub fn off(&mut self) {
+        let _ = self.try_off();
+    }
+
+    /// Try to turn shift registers off.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set the control pin.
+    pub fn try_off(&mut self) -> Result<(), Error> {
         if self.on {
-            self.connector.set(Pin::Control, false);
+            self.connector.try_set(Pin::Control, self.active_low)?;
             self.on = false;
         }
+
+        Ok(())
     }
 
     /// Shift bits into TPIC6C596 shift registers.
-    pub fn shift(&mut self, data: u64, len: usize) {
-        shift(&mut self.connector, data, len);
+    ///
+    /// Shifts the first `len` bits of `data`, starting at bit `0`.
+    /// Connector errors are ignored, see `try_shift/2`.
+    pub fn shift(&mut self, data: impl Bits, len: usize) {
+        let _ = self.try_shift(data, len);
+    }
+
+    /// Try to shift bits into TPIC6C596 shift registers.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    /// Shifting stops at the first error.
+    pub fn try_shift(&mut self, data: impl Bits, len: usize) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        self.tracking.invalidate();
+
+        self.shift_frame(&data, len)
     }
 
     /// Shift a single high (1) bit into TPIC6C596 shift registers.
+    ///
+    /// Connector errors are ignored, see `try_shift_high/0`.
     pub fn shift_high(&mut self) {
-        shift(&mut self.connector, 1, 1);
+        let _ = self.try_shift_high();
+    }
+
+    /// Try to shift a single high (1) bit into TPIC6C596 shift registers.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    pub fn try_shift_high(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        self.tracking.invalidate();
+
+        self.shift_frame(&1_u8, 1)
     }
 
     /// Shift a single low (0) bit into TPIC6C596 shift registers.
+    ///
+    /// Connector errors are ignored, see `try_shift_low/0`.
     pub fn shift_low(&mut self) {
-        shift(&mut self.connector, 0, 1);
+        let _ = self.try_shift_low();
+    }
+
+    /// Try to shift a single low (0) bit into TPIC6C596 shift registers.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    pub fn try_shift_low(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        self.tracking.invalidate();
+
+        self.shift_frame(&0_u8, 1)
     }
 
     /// Write bits into TPIC6C596 shift registers.
     ///
     /// This always shifts the exact number of bits to match the register count.
-    pub fn write(&mut self, data: u64) {
-        shift(&mut self.connector, data, self.bits);
+    /// Missing bits, for example when writing a `u64` to a chain of more
+    /// than 8 registers, are shifted as low (0).
+    /// Connector errors are ignored, see `try_write/1`.
+    pub fn write(&mut self, data: impl Bits) {
+        let _ = self.try_write(data);
+    }
+
+    /// Try to write bits into TPIC6C596 shift registers.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    /// Writing stops at the first error.
+    pub fn try_write(&mut self, data: impl Bits) -> Result<(), Error> {
+        let result = self.write_frame(&data);
+
+        #[cfg(feature = "std")]
+        match result {
+            Ok(()) => self.tracking.written(&data),
+            Err(_) => self.tracking.invalidate(),
+        }
+        result
+    }
+
+    /// Shift a frame in the bit and register order, or layout, and latch it.
+    fn write_frame(&mut self, data: &dyn Bits) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        if let Some(layout) = &self.layout {
+            let frame = layout.frame(&data, self.chain);
+            return self.shift_frame(&frame.as_slice(), self.bits);
+        }
+
+        if (self.bit_order, self.register_order) == (BitOrder::LsbFirst, RegisterOrder::Forward) {
+            return self.shift_frame(data, self.bits);
+        }
+
+        let ordered = order::Ordered {
+            frame: data,
+            chain: self.chain,
+            bits: self.bit_order,
+            registers: self.register_order,
+        };
+        self.shift_frame(&ordered, self.bits)
     }
 
     /// Reset shift registers to 0.
@@ -233,26 +862,198 @@ impl<C: Connector> Controller<C> {
     pub fn reset(&mut self) {
         self.write(0);
     }
+
+    /// Clear the shift registers by pulsing `Pin::Clear` (SRCLR).
+    ///
+    /// Unlike `reset/0` this does not latch, the outputs keep their state.
+    /// Connector errors are ignored, see `try_clear/0`.
+    pub fn clear(&mut self) {
+        let _ = self.try_clear();
+    }
+
+    /// Try to clear the shift registers by pulsing `Pin::Clear` (SRCLR).
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set the clear pin,
+    /// for example `Error::UnsupportedPin` when it is not wired.
+    pub fn try_clear(&mut self) -> Result<(), Error> {
+        self.connector.try_set(Pin::Clear, false)?;
+        self.connector.try_set(Pin::Clear, true)
+    }
+
+    /// Try to reset shift registers to 0.
+    ///
+    /// Same as `try_write(0)`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    pub fn try_reset(&mut self) -> Result<(), Error> {
+        self.try_write(0)
+    }
+
+    /// Detect the register chain length using the serial output (SER OUT).
+    ///
+    /// Connector errors are ignored, see `try_detect_chain/1`.
+    pub fn detect_chain(&mut self, max: usize) -> Option<usize> {
+        self.try_detect_chain(max).ok().flatten()
+    }
+
+    /// Try to detect the register chain length using the serial output (SER OUT).
+    ///
+    /// Flushes up to `max` registers with low bits, shifts in a probe pattern,
+    /// and counts the clock cycles until the pattern appears on the serial output.
+    /// On success the controller uses the detected chain length. A layout is dropped
+    /// when the length differs, as it describes the registers of the configured chain.
+    ///
+    /// Nothing is latched, the outputs keep their state while the shift registers
+    /// are left holding the probe. Returns `None` when the probe does not arrive
+    /// intact within `max` registers.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin or read the serial output,
+    /// for example `Error::NoSerialOut` when it is not wired.
+    pub fn try_detect_chain(&mut self, max: usize) -> Result<Option<usize>, Error> {
+        let bits = max * 8;
+
+        for _ in 0..bits {
+            self.clock_bit(false)?;
+        }
+
+        // Clock cycles until the first probe bit reached the serial output.
+        let mut arrival = None;
+
+        for clock in 1..=bits + 7 {
+            self.clock_bit(PROBE.bit(clock - 1))?;
+            // The serial output follows on the falling clock edge.
+            self.connector.try_set(Pin::Clock, false)?;
+            let serial_out = self.connector.try_serial_out()?;
+
+            match arrival {
+                None if serial_out => arrival = Some(clock),
+                Some(start) if serial_out != PROBE.bit(clock - start) => return Ok(None),
+                Some(start) if clock - start == 7 => break,
+                _ => {}
+            }
+        }
+
+        match arrival {
+            Some(clocks) if clocks <= bits && clocks % 8 == 0 => {
+                #[cfg(feature = "std")]
+                {
+                    if clocks != self.bits {
+                        self.layout = None;
+                    }
+                    if self.layout.is_none() {
+                        self.tracking = tracked::Tracking::new(clocks);
+                    }
+                }
+
+                self.chain = clocks / 8;
+                self.bits = clocks;
+
+                Ok(Some(self.chain))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Shift the first `len` bits of `data` and latch them.
+    ///
+    /// Paced by the controller when a clock frequency or latch pulse is set,
+    /// by the connector otherwise.
+    fn shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        {
+            if self.clocking.is_paced() {
+                return self.clock_frame(data, len);
+            }
+
+            let start = std::time::Instant::now();
+            self.connector.try_shift_frame(data, len)?;
+            self.clocking.record(len, start.elapsed());
+            Ok(())
+        }
+
+        #[cfg(not(feature = "std"))]
+        self.connector.try_shift_frame(data, len)
+    }
+
+    /// Clock the first `len` bits of `data` and latch them, paced and recorded
+    /// in the clock statistics.
+    #[cfg(feature = "std")]
+    fn clock_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        let start = std::time::Instant::now();
+
+        for index in 0..len {
+            self.clock_bit(data.bit(index))?;
+        }
+
+        if self.clocking.latch_pulse.is_zero() {
+            latch(&mut self.connector)?;
+        } else {
+            self.connector.try_set(Pin::Latch, true)?;
+            self.clocking.hold_latch();
+            self.connector.try_set(Pin::Latch, false)?;
+        }
+
+        self.clocking.record(len, start.elapsed());
+        Ok(())
+    }
+
+    /// Clock a single bit into the shift registers, without latching.
+    fn clock_bit(&mut self, bit: bool) -> Result<(), Error> {
+        self.connector.try_set(Pin::Clock, false)?;
+        self.connector.try_set(Pin::Data, bit)?;
+        #[cfg(feature = "std")]
+        self.clocking.hold_clock();
+
+        self.connector.try_set(Pin::Clock, true)?;
+        #[cfg(feature = "std")]
+        self.clocking.hold_clock();
+
+        Ok(())
+    }
 }
 
+/// Probe pattern shifted through the chain by `Controller::try_detect_chain`.
+///
+/// Starts with a high bit, marking its arrival on the serial output.
+const PROBE: u8 = 0b1011_0011;
+
 #[cfg(feature = "delay")]
 /// Latch delay to make the TPIC6C596 properly detect the latch.
-const LATCH_DELAY: std::time::Duration = std::time::Duration::from_nanos(1);
+const LATCH_DELAY: core::time::Duration = core::time::Duration::from_nanos(1);
 
-/// Write bits to the shift register.
-fn shift<C: Connector>(connector: &mut C, mut data: u64, len: usize) {
-    for _ in 0..len {
-        connector.set(Pin::Clock, false);
-        connector.set(Pin::Data, data & 0b1 == 1);
-        connector.set(Pin::Clock, true);
-
-        data >>= 1;
+/// Clock the first `len` bits of `data` through `Pin::Data` and `Pin::Clock` and latch them.
+///
+/// The default `Connector::try_shift_frame`, for connectors that override it conditionally.
+pub(crate) fn clock_frame<C: Connector + ?Sized>(
+    connector: &mut C,
+    data: &dyn Bits,
+    len: usize,
+) -> Result<(), Error> {
+    for index in 0..len {
+        connector.try_set(Pin::Clock, false)?;
+        connector.try_set(Pin::Data, data.bit(index))?;
+        connector.try_set(Pin::Clock, true)?;
     }
 
-    connector.set(Pin::Latch, true);
+    latch(connector)
+}
+
+/// Latch shifted bits into the register outputs.
+///
+/// # Errors
+///
+/// Errors when the connector fails to set the latch pin.
+pub(crate) fn latch<C: Connector + ?Sized>(connector: &mut C) -> Result<(), Error> {
+    connector.try_set(Pin::Latch, true)?;
     #[cfg(feature = "delay")]
     std::thread::sleep(LATCH_DELAY);
-    connector.set(Pin::Latch, false);
+    connector.try_set(Pin::Latch, false)
 }
 
 #[cfg(all(test, feature = "emulator"))]
@@ -261,9 +1062,13 @@ mod tests {
 
     /// Create a controller attach to an emulator for testing.
     fn emulator_controller() -> Controller<Emulator> {
-        const CHAIN: usize = 3;
-        let emulator = Emulator::new(CHAIN);
-        let mut controller = Controller::connect(emulator, CHAIN);
+        chain_controller(3)
+    }
+
+    /// Create a controller attach to an emulator with a specific chain length.
+    fn chain_controller(chain: usize) -> Controller<Emulator> {
+        let emulator = Emulator::new(chain);
+        let mut controller = Controller::connect(emulator, chain);
         controller.on();
 
         controller
@@ -338,4 +1143,306 @@ mod tests {
         assert_eq!(controller.connector().register(1).state(), 0);
         assert_eq!(controller.connector().register(2).state(), 0);
     }
+
+    #[test]
+    fn write_bytes_matches_integer() {
+        let mut integer = emulator_controller();
+        let mut bytes = emulator_controller();
+
+        integer.write(0b0000_1111_1010_0000_0000_0101);
+        bytes.write([0b0000_0101, 0b1010_0000, 0b0000_1111]);
+
+        for index in 0..3 {
+            assert_eq!(
+                integer.connector().register(index).state(),
+                bytes.connector().register(index).state()
+            );
+        }
+    }
+
+    #[test]
+    fn write_long_chain() {
+        const CHAIN: usize = 16;
+        let mut controller = chain_controller(CHAIN);
+
+        let frame: Vec<u8> = (0..CHAIN)
+            .map(|n| u8::try_from(n * 37 % 256).expect("byte"))
+            .collect();
+        controller.write(&frame);
+
+        // The first byte shifted ends up furthest down the chain, bit reversed.
+        for (index, byte) in frame.iter().rev().enumerate() {
+            assert_eq!(
+                controller.connector().register(index).state(),
+                byte.reverse_bits()
+            );
+        }
+
+        controller.reset();
+        for register in controller.connector().registers() {
+            assert_eq!(register.state(), 0);
+        }
+    }
+
+    #[test]
+    fn write_long_chain_pads_short_data() {
+        let mut controller = chain_controller(10);
+        controller.write([0xFF; 10]);
+
+        controller.write(u64::MAX);
+        for index in 0..2 {
+            assert_eq!(controller.connector().register(index).state(), 0);
+        }
+        for index in 2..10 {
+            assert_eq!(controller.connector().register(index).state(), 0xFF);
+        }
+    }
+
+    #[test]
+    fn shift_past_64_bits() {
+        let mut controller = chain_controller(10);
+        let mut frame = [0_u8; 10];
+        frame[9] = 0b1000_0000;
+
+        // Bit 79 is shifted last, so it lands on the first register.
+        controller.shift(frame, 80);
+        assert_eq!(controller.connector().register(0).state(), 0b0000_0001);
+
+        controller.shift([true].as_slice(), 1);
+        assert_eq!(controller.connector().register(0).state(), 0b0000_0011);
+        assert_eq!(controller.connector().register(9).state(), 0);
+    }
+
+    /// Connector that fails after a number of pin changes.
+    #[derive(Debug)]
+    struct Failing(usize);
+
+    impl Connector for Failing {
+        fn set(&mut self, _pin: Pin, _state: bool) {}
+
+        fn get(&self, _pin: Pin) -> bool {
+            false
+        }
+
+        fn try_set(&mut self, _pin: Pin, _state: bool) -> Result<(), Error> {
+            if self.0 == 0 {
+                return Err(Error::Disconnected);
+            }
+
+            self.0 -= 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn try_write_reports_errors() {
+        // Latch on connect, 3 changes per bit, and 2 latch changes.
+        let mut controller = Controller::try_connect(Failing(1 + 24 + 2), 1).expect("connect");
+
+        assert!(controller.try_write(0xFF).is_ok());
+        assert!(matches!(
+            controller.try_write(0xFF),
+            Err(Error::Disconnected)
+        ));
+        assert!(matches!(controller.try_on(), Err(Error::Disconnected)));
+        assert!(controller.try_off().is_ok());
+
+        // Infallible layer ignores the error.
+        controller.write(0xFF);
+        controller.on();
+    }
+
+    #[test]
+    fn try_connect_reports_errors() {
+        assert!(matches!(
+            Controller::try_connect(Failing(0), 1),
+            Err(Error::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn active_low_control() {
+        let emulator = Emulator::with_wiring(3, Wiring::Datasheet);
+        let mut controller = Controller::connect(emulator, 3).with_active_low_control();
+        controller.write(0b1010_0101);
+
+        controller.off();
+        assert!(!controller.connector().is_on());
+        assert_eq!(controller.connector().register(2).state(), 0);
+
+        controller.on();
+        assert!(controller.connector().is_on());
+        assert_eq!(controller.connector().register(2).state(), 0b1010_0101);
+    }
+
+    #[test]
+    fn clear() {
+        let emulator = Emulator::with_wiring(1, Wiring::Datasheet);
+        let mut controller = Controller::connect(emulator, 1).with_active_low_control();
+        controller.write(0xFF);
+
+        controller.try_clear().expect("clear");
+        assert_eq!(controller.connector().register(0).state(), 0xFF);
+
+        controller.shift_high();
+        assert_eq!(controller.connector().register(0).state(), 0b1);
+    }
+
+    #[test]
+    fn layout() {
+        let layout = Layout::new([(0, 0), (1, 7), (1, 0)]).expect("layout");
+        let mut controller = chain_controller(2);
+        assert_eq!(controller.lights(), 16);
+        controller = controller.with_layout(layout);
+        assert_eq!(controller.lights(), 3);
+        controller.write(0b011_u8);
+
+        assert_eq!(controller.connector().register(0).state(), 0b0000_0001);
+        assert_eq!(controller.connector().register(1).state(), 0b1000_0000);
+
+        let mut identity = chain_controller(2).with_layout(Layout::identity(2));
+        let mut reference = chain_controller(2);
+        identity.write(0x1234_u16);
+        reference.write(0x1234_u16);
+        assert_eq!(
+            identity.connector().registers(),
+            reference.connector().registers()
+        );
+    }
+
+    #[test]
+    fn detect_chain() {
+        for chain in [1, 3, 12] {
+            let mut controller = chain_controller(chain);
+            controller.write([0xA5; 12]);
+
+            let mut detected = Controller::connect(Emulator::new(chain), 16);
+            assert_eq!(detected.try_detect_chain(16).ok(), Some(Some(chain)));
+            assert_eq!(detected.register_chain(), chain);
+
+            // The probe is not latched.
+            assert_eq!(controller.detect_chain(16), Some(chain));
+            assert!(controller
+                .connector()
+                .registers()
+                .iter()
+                .all(|register| register.state() == 0xA5));
+        }
+
+        let emulator = Emulator::with_wiring(5, Wiring::Datasheet);
+        let mut controller = Controller::connect(emulator, 1)
+            .with_active_low_control()
+            .batched();
+        assert_eq!(controller.detect_chain(8), Some(5));
+        assert_eq!(controller.register_chain(), 5);
+
+        // Too short to reach the serial output.
+        let mut controller = chain_controller(4);
+        assert_eq!(controller.try_detect_chain(3).ok(), Some(None));
+        assert_eq!(controller.register_chain(), 4);
+    }
+
+    #[test]
+    fn detect_chain_with_layout() {
+        let layout = Layout::new([(0, 0), (1, 7)]).expect("layout");
+
+        let mut controller = chain_controller(2).with_layout(layout.clone());
+        assert_eq!(controller.detect_chain(8), Some(2));
+        assert_eq!(controller.layout(), Some(&layout));
+        assert_eq!(controller.lights(), 2);
+
+        // The layout describes two registers, not the detected three.
+        let mut controller = Controller::connect(Emulator::new(3), 2).with_layout(layout);
+        assert_eq!(controller.detect_chain(8), Some(3));
+        assert_eq!(controller.layout(), None);
+        assert_eq!(controller.lights(), 24);
+
+        let mut reference = chain_controller(3);
+        reference.write(0x0081_u32);
+        controller.on();
+        controller.write(0x0081_u32);
+        assert_eq!(
+            controller.connector().registers(),
+            reference.connector().registers()
+        );
+    }
+
+    #[test]
+    fn detect_chain_without_serial_out() {
+        let mut controller = Controller::try_connect(Failing(usize::MAX), 1).expect("connect");
+
+        assert!(matches!(
+            controller.try_detect_chain(1),
+            Err(Error::NoSerialOut)
+        ));
+        assert_eq!(controller.detect_chain(1), None);
+    }
+
+    #[test]
+    fn bit_and_register_order() {
+        let orders = [
+            (BitOrder::LsbFirst, RegisterOrder::Forward),
+            (BitOrder::LsbFirst, RegisterOrder::Reverse),
+            (BitOrder::MsbFirst, RegisterOrder::Forward),
+            (BitOrder::MsbFirst, RegisterOrder::Reverse),
+        ];
+
+        for chain in 1..=4 {
+            for (bit_order, register_order) in orders {
+                let mut controller = chain_controller(chain)
+                    .with_bit_order(bit_order)
+                    .with_register_order(register_order);
+
+                for byte in 0..chain {
+                    for bit in 0..8 {
+                        let mut frame = vec![0_u8; chain];
+                        frame[byte] = 1 << bit;
+                        controller.write(&frame);
+
+                        let register = match register_order {
+                            RegisterOrder::Forward => chain - 1 - byte,
+                            RegisterOrder::Reverse => byte,
+                        };
+                        let output = match bit_order {
+                            BitOrder::LsbFirst => 7 - bit,
+                            BitOrder::MsbFirst => bit,
+                        };
+
+                        for (index, state) in controller.connector().registers().iter().enumerate()
+                        {
+                            let expected = if index == register { 1 << output } else { 0 };
+                            assert_eq!(
+                                state.state(),
+                                expected,
+                                "{bit_order:?} {register_order:?}, chain {chain}, bit {bit} of byte {byte}, register {index}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn order_all_bytes() {
+        let mut controller = chain_controller(2)
+            .with_bit_order(BitOrder::MsbFirst)
+            .with_register_order(RegisterOrder::Reverse);
+
+        for byte in 0..=u8::MAX {
+            controller.write([byte, !byte]);
+
+            assert_eq!(controller.connector().register(0).state(), byte);
+            assert_eq!(controller.connector().register(1).state(), !byte);
+        }
+
+        // Short frames are padded, raw shifts are not reordered.
+        controller.write(0x01_u8);
+        assert_eq!(controller.connector().register(0).state(), 0x01);
+        assert_eq!(controller.connector().register(1).state(), 0);
+
+        controller.shift(0x01_u8, 8);
+        assert_eq!(controller.connector().register(0).state(), 0x80);
+        assert_eq!(controller.connector().register(1).state(), 0x01);
+    }
 }
@@ -0,0 +1,508 @@
+//! Parallel chains sharing the clock, latch, and control pins.
+//!
+//! Every chain has its own data pin, all chains are shifted on the same clock edges.
+//! Writing N chains takes as many clock cycles as writing the longest chain,
+//! instead of one long chain of all registers.
+
+use crate::{BitOrder, Bits, Connector, Controller, Error, Layout, Pin, RegisterOrder};
+
+/// Connector with a data pin per chain.
+///
+/// `Pin::Data` sets all data pins at once,
+/// other pins are shared by all chains.
+pub trait MultiConnector: Connector {
+    /// Number of data pins, one per chain.
+    #[must_use]
+    fn data_pins(&self) -> usize;
+
+    /// Try to set the data pins, `states[n]` on the data pin of chain `n`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when a data pin could not be set.
+    fn try_set_data(&mut self, states: &[bool]) -> Result<(), Error>;
+}
+
+/// A controller to manage parallel TPIC6C596 register chains.
+///
+/// Built on a `Controller` of all registers, so its settings like the active low control,
+/// bit and register order, and layout apply as well.
+/// The chains act as a single chain of all registers, chain `0` nearest to the controller,
+/// see `write_all/1`. `write/1` takes a frame per chain instead.
+#[derive(Debug)]
+pub struct MultiController<C: MultiConnector> {
+    /// Controller of all registers, shifting the chains in parallel.
+    controller: Controller<Parallel<C>>,
+}
+
+impl<C: MultiConnector> MultiController<C> {
+    /// Connect controller to parallel TPIC6C596 chains, with `chains[n]` registers in chain `n`.
+    ///
+    /// Connector errors are ignored, see `try_connect/2`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the number of chains differs from the connector data pins.
+    #[must_use]
+    pub fn connect(connector: C, chains: &[usize]) -> Self {
+        let (parallel, registers) = Parallel::new(connector, chains);
+
+        Self {
+            controller: Controller::connect(parallel, registers),
+        }
+    }
+
+    /// Connect controller to parallel TPIC6C596 chains, with `chains[n]` registers in chain `n`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to reset the latch or read the control pin.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the number of chains differs from the connector data pins.
+    pub fn try_connect(connector: C, chains: &[usize]) -> Result<Self, Error> {
+        let (parallel, registers) = Parallel::new(connector, chains);
+
+        Ok(Self {
+            controller: Controller::try_connect(parallel, registers)?,
+        })
+    }
+
+    /// Treat `Pin::Control` as active low, see `Controller::with_active_low_control`.
+    #[must_use]
+    pub fn with_active_low_control(mut self) -> Self {
+        self.controller = self.controller.with_active_low_control();
+        self
+    }
+
+    /// Shift the bits of every frame byte in `order`, see `Controller::with_bit_order`.
+    #[must_use]
+    pub fn with_bit_order(mut self, order: BitOrder) -> Self {
+        self.controller = self.controller.with_bit_order(order);
+        self
+    }
+
+    /// Assign frame bytes to registers in `order`, see `Controller::with_register_order`.
+    ///
+    /// Applies to the frame of every chain written using `write/1`,
+    /// and to the frame of all registers written using `write_all/1`.
+    #[must_use]
+    pub fn with_register_order(mut self, order: RegisterOrder) -> Self {
+        self.controller = self.controller.with_register_order(order);
+        self
+    }
+
+    /// Map logical lights to the outputs of all registers, see `Controller::with_layout`.
+    ///
+    /// Register `n` of the layout is register `n` of the chains taken as a single chain,
+    /// see `write_all/1`.
+    #[must_use]
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.controller = self.controller.with_layout(layout);
+        self
+    }
+
+    /// Controller connector.
+    #[must_use]
+    pub const fn connector(&self) -> &C {
+        &self.controller.connector().connector
+    }
+
+    /// The length of every register chain.
+    #[must_use]
+    pub fn register_chains(&self) -> &[usize] {
+        &self.controller.connector().chains
+    }
+
+    /// Number of lights in the frames written using `write_all/1`, see `Controller::lights`.
+    #[must_use]
+    pub fn lights(&self) -> usize {
+        self.controller.lights()
+    }
+
+    /// Turn shift registers on.
+    ///
+    /// Connector errors are ignored, see `try_on/0`.
+    pub fn on(&mut self) {
+        self.controller.on();
+    }
+
+    /// Try to turn shift registers on.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set the control pin.
+    pub fn try_on(&mut self) -> Result<(), Error> {
+        self.controller.try_on()
+    }
+
+    /// Turn shift registers off.
+    ///
+    /// Connector errors are ignored, see `try_off/0`.
+    pub fn off(&mut self) {
+        self.controller.off();
+    }
+
+    /// Try to turn shift registers off.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set the control pin.
+    pub fn try_off(&mut self) -> Result<(), Error> {
+        self.controller.try_off()
+    }
+
+    /// Write a frame per chain, `frames[n]` to chain `n`.
+    ///
+    /// Every chain shows its frame as if written using `Controller::write`,
+    /// in the bit and register order of the controller.
+    /// Missing frames and bits are shifted as low (0).
+    /// Use `write_all/1` with a layout, the frames are taken as lights otherwise.
+    /// Connector errors are ignored, see `try_write/1`.
+    pub fn write<B: Bits>(&mut self, frames: &[B]) {
+        let _ = self.try_write(frames);
+    }
+
+    /// Try to write a frame per chain, `frames[n]` to chain `n`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    /// Writing stops at the first error.
+    pub fn try_write<B: Bits>(&mut self, frames: &[B]) -> Result<(), Error> {
+        let chains = &self.controller.connector().chains;
+        let mut frame = vec![0_u8; chains.iter().sum()];
+        let mut offset = 0;
+
+        // Frame byte `0` ends up furthest from the controller in the forward register order,
+        // nearest to it in the reverse order, the chains are joined accordingly.
+        let mut joined: Vec<_> = chains.iter().zip(0..).collect();
+        if self.controller.register_order() == RegisterOrder::Forward {
+            joined.reverse();
+        }
+
+        for (chain, index) in joined {
+            for bit in (0..chain * 8).filter(|bit| frames.get(index).is_some_and(|f| f.bit(*bit))) {
+                let bit = offset * 8 + bit;
+                frame[bit / 8] |= 1 << (bit % 8);
+            }
+            offset += chain;
+        }
+
+        self.controller.try_write(frame)
+    }
+
+    /// Write a frame of all registers, as if the chains were a single chain.
+    ///
+    /// The chains are taken in order, chain `0` nearest to the controller,
+    /// so register `n` of chain `0` is register `n` of the frame.
+    /// Connector errors are ignored, see `try_write_all/1`.
+    pub fn write_all(&mut self, data: impl Bits) {
+        let _ = self.try_write_all(data);
+    }
+
+    /// Try to write a frame of all registers, as if the chains were a single chain.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    /// Writing stops at the first error.
+    pub fn try_write_all(&mut self, data: impl Bits) -> Result<(), Error> {
+        self.controller.try_write(data)
+    }
+
+    /// Reset shift registers to 0.
+    pub fn reset(&mut self) {
+        self.controller.reset();
+    }
+}
+
+/// Connector shifting the frame of all registers into parallel chains.
+#[derive(Debug)]
+struct Parallel<C: MultiConnector> {
+    /// Connector with a data pin per chain.
+    connector: C,
+
+    /// Register chain length per chain.
+    chains: Vec<usize>,
+}
+
+impl<C: MultiConnector> Parallel<C> {
+    /// Shift into `chains`, returns the connector and the number of registers.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the number of chains differs from the connector data pins.
+    fn new(connector: C, chains: &[usize]) -> (Self, usize) {
+        assert_eq!(connector.data_pins(), chains.len(), "a data pin per chain");
+
+        let parallel = Self {
+            connector,
+            chains: chains.to_vec(),
+        };
+        (parallel, chains.iter().sum())
+    }
+}
+
+impl<C: MultiConnector> Connector for Parallel<C> {
+    fn get(&self, pin: Pin) -> bool {
+        self.connector.get(pin)
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        self.connector.set(pin, state);
+    }
+
+    fn try_get(&self, pin: Pin) -> Result<bool, Error> {
+        self.connector.try_get(pin)
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.connector.try_set(pin, state)
+    }
+
+    /// Shift the frame in parallel, the last bits of `data` into chain `0`,
+    /// the bits before into chain `1`, and so on.
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        let bits = self.chains.iter().max().copied().unwrap_or(0) * 8;
+        let mut states = vec![false; self.chains.len()];
+
+        // Index in `data` of the first bit of every chain.
+        let mut end = len;
+        let starts: Vec<_> = self
+            .chains
+            .iter()
+            .map(|chain| {
+                end = end.saturating_sub(chain * 8);
+                end
+            })
+            .collect();
+
+        for index in 0..bits {
+            self.connector.try_set(Pin::Clock, false)?;
+
+            // Shorter chains shift their padding first, so it drops out of the chain.
+            for (chain, state) in states.iter_mut().enumerate() {
+                let padding = bits - self.chains[chain] * 8;
+
+                *state = index >= padding && {
+                    let bit = starts[chain] + index - padding;
+                    bit < len && data.bit(bit)
+                };
+            }
+
+            self.connector.try_set_data(&states)?;
+            self.connector.try_set(Pin::Clock, true)?;
+        }
+
+        crate::latch(&mut self.connector)
+    }
+}
+
+/// Emulator of parallel chains sharing the clock, latch, and control pins.
+#[cfg(feature = "emulator")]
+#[derive(Debug)]
+pub struct MultiEmulator {
+    /// Emulated chains.
+    chains: Vec<crate::Emulator>,
+}
+
+#[cfg(feature = "emulator")]
+impl MultiEmulator {
+    /// Creates a new emulator with `chains[n]` registers in chain `n`.
+    #[must_use]
+    pub fn new(chains: &[usize]) -> Self {
+        Self::with_wiring(chains, crate::Wiring::Simple)
+    }
+
+    /// Creates a new emulator with a specific pin wiring for all chains.
+    #[must_use]
+    pub fn with_wiring(chains: &[usize], wiring: crate::Wiring) -> Self {
+        Self {
+            chains: chains
+                .iter()
+                .map(|chain| crate::Emulator::with_wiring(*chain, wiring))
+                .collect(),
+        }
+    }
+
+    /// Retrieves the emulator of a chain.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of range.
+    #[must_use]
+    pub fn chain(&self, index: usize) -> &crate::Emulator {
+        &self.chains[index]
+    }
+
+    /// Retrieves the emulators of all chains.
+    #[must_use]
+    pub fn chains(&self) -> &[crate::Emulator] {
+        &self.chains
+    }
+}
+
+#[cfg(feature = "emulator")]
+impl Connector for MultiEmulator {
+    fn get(&self, pin: Pin) -> bool {
+        self.chains.first().is_some_and(|chain| chain.get_pin(pin))
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        for chain in &mut self.chains {
+            chain.set_pin(pin, state);
+        }
+    }
+}
+
+#[cfg(feature = "emulator")]
+impl MultiConnector for MultiEmulator {
+    fn data_pins(&self) -> usize {
+        self.chains.len()
+    }
+
+    fn try_set_data(&mut self, states: &[bool]) -> Result<(), Error> {
+        for (chain, state) in self.chains.iter_mut().zip(states) {
+            chain.set_pin(Pin::Data, *state);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Controller;
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn parallel_chains() {
+        use crate::Emulator;
+
+        let chains = [3, 1, 2];
+        let frames = [0x00AB_CDEF_u32, 0x0000_0081, 0x0000_F00F];
+
+        let mut controller = MultiController::connect(MultiEmulator::new(&chains), &chains);
+        controller.on();
+        controller.write(&frames);
+
+        for (index, (chain, frame)) in chains.into_iter().zip(frames).enumerate() {
+            let mut reference = Controller::connect(Emulator::new(chain), chain);
+            reference.on();
+            reference.write(frame);
+
+            assert_eq!(
+                controller.connector().chain(index).registers(),
+                reference.connector().registers(),
+                "chain {index}"
+            );
+        }
+
+        controller.reset();
+        controller.off();
+        for chain in controller.connector().chains() {
+            assert!(!chain.is_on());
+            assert!(chain
+                .registers()
+                .iter()
+                .all(|register| register.state() == 0));
+        }
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn settings() {
+        use crate::{Emulator, Wiring};
+
+        let chains = [2, 1];
+        let frames = [0x0180_u16, 0x00C3];
+
+        for order in [RegisterOrder::Forward, RegisterOrder::Reverse] {
+            let emulator = MultiEmulator::with_wiring(&chains, Wiring::Datasheet);
+            let mut controller = MultiController::connect(emulator, &chains)
+                .with_active_low_control()
+                .with_bit_order(BitOrder::MsbFirst)
+                .with_register_order(order);
+            controller.on();
+            controller.write(&frames);
+
+            for (index, (chain, frame)) in chains.into_iter().zip(frames).enumerate() {
+                let emulator = Emulator::with_wiring(chain, Wiring::Datasheet);
+                let mut reference = Controller::connect(emulator, chain)
+                    .with_active_low_control()
+                    .with_bit_order(BitOrder::MsbFirst)
+                    .with_register_order(order);
+                reference.on();
+                reference.write(frame);
+
+                assert!(controller.connector().chain(index).is_on());
+                assert_eq!(
+                    controller.connector().chain(index).registers(),
+                    reference.connector().registers(),
+                    "{order:?} chain {index}"
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn layout() {
+        let chains = [1, 2];
+        let layout = Layout::new([(0, 0), (1, 7), (2, 3)]).expect("layout");
+        let mut controller =
+            MultiController::connect(MultiEmulator::new(&chains), &chains).with_layout(layout);
+        assert_eq!(controller.lights(), 3);
+
+        controller.on();
+        controller.write_all(0b111_u8);
+
+        let emulator = controller.connector();
+        assert_eq!(emulator.chain(0).register(0).state(), 0b0000_0001);
+        assert_eq!(emulator.chain(1).register(0).state(), 0b1000_0000);
+        assert_eq!(emulator.chain(1).register(1).state(), 0b0000_1000);
+    }
+
+    #[test]
+    fn clock_cycles() {
+        /// Counts rising clock edges.
+        #[derive(Debug, Default)]
+        struct Clocks(usize, bool);
+
+        impl Connector for Clocks {
+            fn set(&mut self, pin: Pin, state: bool) {
+                if pin == Pin::Clock && state && !self.1 {
+                    self.0 += 1;
+                }
+                if pin == Pin::Clock {
+                    self.1 = state;
+                }
+            }
+
+            fn get(&self, _pin: Pin) -> bool {
+                false
+            }
+        }
+
+        impl MultiConnector for Clocks {
+            fn data_pins(&self) -> usize {
+                2
+            }
+
+            fn try_set_data(&mut self, _states: &[bool]) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        let mut controller = MultiController::connect(Clocks::default(), &[4, 4]);
+        controller.write(&[u32::MAX, u32::MAX]);
+        assert_eq!(controller.connector().0, 32);
+
+        let mut single = Controller::connect(Clocks::default(), 8);
+        single.write(u64::MAX);
+        assert_eq!(single.connector().0, 64);
+    }
+}
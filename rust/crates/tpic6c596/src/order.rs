@@ -0,0 +1,61 @@
+//! Bit and register order of written frames.
+//!
+//! By default frame byte `0` is shifted first, least significant bit first,
+//! so it ends up bit reversed on the register furthest from the controller.
+
+use crate::Bits;
+
+/// Order in which the bits of a frame byte are shifted into its register.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    /// Bit `0` is shifted first and ends up on output 7.
+    #[default]
+    LsbFirst,
+
+    /// Bit `7` is shifted first, bit `n` ends up on output `n`.
+    MsbFirst,
+}
+
+/// Order in which frame bytes are assigned to the registers of the chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RegisterOrder {
+    /// Byte `0` is shifted first and ends up on the register furthest from the controller.
+    #[default]
+    Forward,
+
+    /// Byte `0` ends up on register `0`, nearest to the controller.
+    Reverse,
+}
+
+/// Frame read in shift order for a chain with a bit and register order.
+pub struct Ordered<'a> {
+    /// Frame, one byte per register.
+    pub frame: &'a dyn Bits,
+
+    /// Register chain length.
+    pub chain: usize,
+
+    /// Bit order.
+    pub bits: BitOrder,
+
+    /// Register order.
+    pub registers: RegisterOrder,
+}
+
+impl Bits for Ordered<'_> {
+    fn bit(&self, index: usize) -> bool {
+        let (byte, bit) = (index / 8, index % 8);
+
+        let byte = match self.registers {
+            RegisterOrder::Forward => byte,
+            RegisterOrder::Reverse if byte < self.chain => self.chain - 1 - byte,
+            RegisterOrder::Reverse => return false,
+        };
+        let bit = match self.bits {
+            BitOrder::LsbFirst => bit,
+            BitOrder::MsbFirst => 7 - bit,
+        };
+
+        self.frame.bit(byte * 8 + bit)
+    }
+}
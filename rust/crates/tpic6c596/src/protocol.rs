@@ -0,0 +1,318 @@
+//! Emulator datagram protocol.
+//!
+//! # Legacy format
+//!
+//! Every byte is a pin change: the pin id in the low nibble
+//! (`Data` 1, `Control` 2, `Clock` 3, `Latch` 4, `Clear` 5)
+//! and the state in the most significant bit. Other bytes are ignored.
+//!
+//! # Version 2
+//!
+//! Datagrams start with an eight byte header:
+//!
+//! | Byte | Content                                             |
+//! |------|-----------------------------------------------------|
+//! | 0    | Magic `0x76`                                        |
+//! | 1    | Protocol version in the high nibble                 |
+//! | 2    | Packet kind                                         |
+//! | 3    | Flags, bit 0 requests an acknowledgement            |
+//! | 4..8 | Sequence number (little endian)                     |
+//!
+//! Versions are stored in the high nibble, so all bytes of a handshake have a low nibble
+//! outside the legacy pin ids. Legacy emulators ignore it and do not answer,
+//! letting clients fall back to the legacy format.
+//!
+//! Timestamped pin changes (`Packet::TimedPins`) are a pin byte followed by the time
+//! of the change in nanoseconds (eight bytes, little endian) on the sender's monotonic
+//! clock, letting the emulator check the timing of batched changes.
+
+use core::time::Duration;
+
+use crate::Pin;
+
+/// Highest supported protocol version.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// First byte of a framed datagram.
+const MAGIC: u8 = 0x76;
+
+/// Header length in bytes.
+const HEADER: usize = 8;
+
+/// Flag requesting an acknowledgement.
+const ACK_REQUESTED: u8 = 0b0000_0001;
+
+/// Emulator protocol packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    /// Version handshake, carrying the highest version supported by the sender.
+    ///
+    /// Always answered by the emulator with its own version.
+    /// Sent without acknowledgement and sequence number zero,
+    /// so legacy emulators ignore it.
+    Hello(u8),
+
+    /// Batched pin changes, applied in order.
+    Pins(Vec<(Pin, bool)>),
+
+    /// Whole frame, clocked in and latched by the emulator.
+    Frame {
+        /// Frame length in bits.
+        bits: usize,
+
+        /// Frame bytes, bit `n` is bit `n % 8` of byte `n / 8`.
+        data: Vec<u8>,
+    },
+
+    /// Acknowledges the datagram with the same sequence number.
+    Ack,
+
+    /// Shared secret authenticating a network client, see `Listener`.
+    ///
+    /// Acknowledged when accepted, the connection is closed otherwise.
+    Auth(Vec<u8>),
+
+    /// Batched pin changes, applied in order, with the time of each change
+    /// since an arbitrary epoch of the sender.
+    TimedPins(Vec<(Pin, bool, Duration)>),
+}
+
+/// Packet kind byte.
+const fn kind(packet: &Packet) -> u8 {
+    match packet {
+        Packet::Hello(_) => 0x00,
+        Packet::Pins(_) => 0x01,
+        Packet::Frame { .. } => 0x02,
+        Packet::Ack => 0x03,
+        Packet::Auth(_) => 0x04,
+        Packet::TimedPins(_) => 0x05,
+    }
+}
+
+/// Emulator protocol datagram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datagram {
+    /// Sequence number, `None` for the legacy format.
+    pub sequence: Option<u32>,
+
+    /// Whether the sender requests an acknowledgement.
+    pub acknowledge: bool,
+
+    /// Packet.
+    pub packet: Packet,
+}
+
+impl Datagram {
+    /// Encode the datagram.
+    ///
+    /// Datagrams without sequence number are encoded in the legacy format,
+    /// which only supports `Packet::Pins`; other packets encode empty.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let Some(sequence) = self.sequence else {
+            return match &self.packet {
+                Packet::Pins(pins) => pins
+                    .iter()
+                    .map(|(pin, state)| encode_pin(*pin, *state))
+                    .collect(),
+                _ => Vec::new(),
+            };
+        };
+
+        let mut bytes = vec![
+            MAGIC,
+            PROTOCOL_VERSION << 4,
+            kind(&self.packet),
+            if self.acknowledge { ACK_REQUESTED } else { 0 },
+        ];
+        bytes.extend_from_slice(&sequence.to_le_bytes());
+
+        match &self.packet {
+            Packet::Hello(version) => bytes.push(version << 4),
+            Packet::Pins(pins) => {
+                bytes.extend(pins.iter().map(|(pin, state)| encode_pin(*pin, *state)));
+            }
+            Packet::Frame { bits, data } => {
+                bytes.extend_from_slice(&u32::try_from(*bits).unwrap_or(u32::MAX).to_le_bytes());
+                bytes.extend_from_slice(data);
+            }
+            Packet::Ack => {}
+            Packet::Auth(token) => bytes.extend_from_slice(token),
+            Packet::TimedPins(pins) => {
+                for (pin, state, at) in pins {
+                    bytes.push(encode_pin(*pin, *state));
+                    bytes.extend_from_slice(
+                        &u64::try_from(at.as_nanos())
+                            .unwrap_or(u64::MAX)
+                            .to_le_bytes(),
+                    );
+                }
+            }
+        }
+
+        bytes
+    }
+
+    /// Decode a datagram in either format.
+    ///
+    /// Returns `None` for malformed framed datagrams or unsupported versions.
+    #[must_use]
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.first() != Some(&MAGIC) {
+            return Some(Self {
+                sequence: None,
+                acknowledge: false,
+                packet: Packet::Pins(bytes.iter().filter_map(|byte| decode_pin(*byte)).collect()),
+            });
+        }
+
+        let header = bytes.get(..HEADER)?;
+        let payload = &bytes[HEADER..];
+
+        // Handshakes are understood across versions.
+        if header[1] >> 4 != PROTOCOL_VERSION && header[2] != 0x00 {
+            return None;
+        }
+
+        let packet = match header[2] {
+            0x00 => Packet::Hello(payload.first()? >> 4),
+            0x01 => Packet::Pins(
+                payload
+                    .iter()
+                    .map(|byte| decode_pin(*byte))
+                    .collect::<Option<_>>()?,
+            ),
+            0x02 => {
+                let bits = u32::from_le_bytes(payload.get(..4)?.try_into().ok()?);
+                let bits = usize::try_from(bits).ok()?;
+                let data = payload[4..].to_vec();
+
+                if data.len() < bits.div_ceil(8) {
+                    return None;
+                }
+
+                Packet::Frame { bits, data }
+            }
+            0x03 => Packet::Ack,
+            0x04 => Packet::Auth(payload.to_vec()),
+            0x05 => Packet::TimedPins(
+                payload
+                    .chunks(9)
+                    .map(|change| {
+                        let (pin, state) = decode_pin(change[0])?;
+                        let at = u64::from_le_bytes(change.get(1..)?.try_into().ok()?);
+
+                        Some((pin, state, Duration::from_nanos(at)))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            sequence: Some(u32::from_le_bytes(header[4..8].try_into().ok()?)),
+            acknowledge: header[3] & ACK_REQUESTED != 0,
+            packet,
+        })
+    }
+}
+
+/// Encode a pin change as legacy byte.
+const fn encode_pin(pin: Pin, state: bool) -> u8 {
+    let state = if state { 0b1000_0000 } else { 0 };
+
+    match pin {
+        Pin::Data => state | 1,
+        Pin::Control => state | 2,
+        Pin::Clock => state | 3,
+        Pin::Latch => state | 4,
+        Pin::Clear => state | 5,
+    }
+}
+
+/// Decode a legacy pin change byte.
+const fn decode_pin(byte: u8) -> Option<(Pin, bool)> {
+    let pin = match byte & 0b0000_1111 {
+        1 => Pin::Data,
+        2 => Pin::Control,
+        3 => Pin::Clock,
+        4 => Pin::Latch,
+        5 => Pin::Clear,
+        _ => return None,
+    };
+
+    Some((pin, byte & 0b1000_0000 != 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy() {
+        let datagram = Datagram::decode(&[0b1000_0011, 0b0000_0001, 0b0000_1001]).expect("legacy");
+
+        assert_eq!(datagram.sequence, None);
+        assert_eq!(
+            datagram.packet,
+            Packet::Pins(vec![(Pin::Clock, true), (Pin::Data, false)])
+        );
+        assert_eq!(datagram.encode(), [0b1000_0011, 0b0000_0001]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for packet in [
+            Packet::Hello(PROTOCOL_VERSION),
+            Packet::Pins(vec![(Pin::Latch, true), (Pin::Clear, false)]),
+            Packet::Frame {
+                bits: 12,
+                data: vec![0b1010_0101, 0b0000_1111],
+            },
+            Packet::Ack,
+            Packet::Auth(b"secret".to_vec()),
+            Packet::TimedPins(vec![
+                (Pin::Clock, true, Duration::from_nanos(40)),
+                (Pin::Data, false, Duration::from_secs(3)),
+            ]),
+        ] {
+            let datagram = Datagram {
+                sequence: Some(0x0102_0304),
+                acknowledge: true,
+                packet,
+            };
+
+            assert_eq!(Datagram::decode(&datagram.encode()), Some(datagram));
+        }
+    }
+
+    #[test]
+    fn handshake_ignored_by_legacy_emulators() {
+        let hello = Datagram {
+            sequence: Some(0),
+            acknowledge: false,
+            packet: Packet::Hello(PROTOCOL_VERSION),
+        }
+        .encode();
+
+        assert!(hello.iter().all(|byte| decode_pin(*byte).is_none()));
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(Datagram::decode(&[MAGIC, 0x20, 0x01]), None);
+        assert_eq!(Datagram::decode(&[MAGIC, 0x30, 0x03, 0, 0, 0, 0, 0]), None);
+        assert_eq!(
+            Datagram::decode(&[MAGIC, 0x20, 0x02, 0, 0, 0, 0, 0, 9, 0, 0, 0, 1]),
+            None
+        );
+        assert_eq!(
+            Datagram::decode(&[MAGIC, 0x20, 0x01, 0, 0, 0, 0, 0, 0x07]),
+            None
+        );
+        assert_eq!(
+            Datagram::decode(&[MAGIC, 0x20, 0x05, 0, 0, 0, 0, 0, 0x01, 0, 0]),
+            None
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Pin change recording in Value Change Dump (VCD) format.
+//!
+//! Traces can be opened in waveform viewers like `GTKWave`.
+
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crate::{Bits, Connector, Error, Pin};
+
+/// Recorded pins and variable names, in declaration order.
+pub const PINS: [(Pin, &str); 5] = [
+    (Pin::Clock, "clock"),
+    (Pin::Control, "control"),
+    (Pin::Data, "data"),
+    (Pin::Latch, "latch"),
+    (Pin::Clear, "clear"),
+];
+
+/// Value Change Dump writer for TPIC6C596 pins and register outputs.
+///
+/// Timestamps are in nanoseconds relative to an arbitrary starting point,
+/// and should not decrease.
+#[derive(Debug)]
+pub struct Vcd<W: Write> {
+    /// Output.
+    writer: W,
+
+    /// Last register output states.
+    registers: Vec<u8>,
+
+    /// Last written timestamp in nanoseconds.
+    time: Option<u64>,
+}
+
+impl<W: Write> Vcd<W> {
+    /// Start a trace with pins and `registers` register outputs.
+    ///
+    /// Writes the VCD header and initial values,
+    /// all low except for the (active low) `Pin::Clear`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when writing fails.
+    pub fn new(mut writer: W, registers: usize) -> std::io::Result<Self> {
+        writeln!(
+            writer,
+            "$version tpic6c596 {} $end",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(writer, "$timescale 1ns $end")?;
+        writeln!(writer, "$scope module tpic6c596 $end")?;
+        for (index, (_, name)) in PINS.iter().enumerate() {
+            writeln!(writer, "$var wire 1 {} {name} $end", identifier(index))?;
+        }
+        for register in 0..registers {
+            let id = identifier(PINS.len() + register);
+            writeln!(writer, "$var wire 8 {id} register{register} $end")?;
+        }
+        writeln!(writer, "$upscope $end")?;
+        writeln!(writer, "$enddefinitions $end")?;
+
+        writeln!(writer, "#0")?;
+        writeln!(writer, "$dumpvars")?;
+        for (index, (pin, _)) in PINS.iter().enumerate() {
+            let state = u8::from(*pin == Pin::Clear);
+            writeln!(writer, "{state}{}", identifier(index))?;
+        }
+        for register in 0..registers {
+            writeln!(writer, "b0 {}", identifier(PINS.len() + register))?;
+        }
+        writeln!(writer, "$end")?;
+
+        Ok(Self {
+            writer,
+            registers: vec![0; registers],
+            time: Some(0),
+        })
+    }
+
+    /// Record a pin change.
+    ///
+    /// # Errors
+    ///
+    /// Errors when writing fails.
+    pub fn pin(&mut self, at: Duration, pin: Pin, state: bool) -> std::io::Result<()> {
+        self.timestamp(at)?;
+        writeln!(self.writer, "{}{}", u8::from(state), identifier(index(pin)))
+    }
+
+    /// Record register output states, only changed registers are written.
+    ///
+    /// # Errors
+    ///
+    /// Errors when writing fails.
+    pub fn registers(&mut self, at: Duration, states: &[u8]) -> std::io::Result<()> {
+        for (register, &state) in states.iter().enumerate().take(self.registers.len()) {
+            if self.registers[register] != state {
+                self.registers[register] = state;
+                self.timestamp(at)?;
+                writeln!(
+                    self.writer,
+                    "b{state:b} {}",
+                    identifier(PINS.len() + register)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush the output.
+    ///
+    /// # Errors
+    ///
+    /// Errors when flushing fails.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Unwrap the output.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write a timestamp, when changed.
+    fn timestamp(&mut self, at: Duration) -> std::io::Result<()> {
+        let time = u64::try_from(at.as_nanos()).unwrap_or(u64::MAX);
+
+        if self.time != Some(time) {
+            self.time = Some(time);
+            writeln!(self.writer, "#{time}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Variable index of a pin in `PINS`.
+const fn index(pin: Pin) -> usize {
+    match pin {
+        Pin::Clock => 0,
+        Pin::Control => 1,
+        Pin::Data => 2,
+        Pin::Latch => 3,
+        Pin::Clear => 4,
+    }
+}
+
+/// VCD identifier code for a variable index.
+fn identifier(mut index: usize) -> String {
+    /// Printable ASCII range used for identifiers.
+    const RANGE: usize = (b'~' - b'!' + 1) as usize;
+    let mut id = String::new();
+
+    loop {
+        #[allow(clippy::cast_possible_truncation)]
+        id.push(char::from(b'!' + (index % RANGE) as u8));
+        index /= RANGE;
+
+        if index == 0 {
+            return id;
+        }
+        index -= 1;
+    }
+}
+
+/// Reads register output states from a connector.
+type Outputs<C> = fn(&C) -> Vec<u8>;
+
+/// Connector recording every pin change to a Value Change Dump.
+///
+/// Forwards every change to the wrapped connector and timestamps it.
+/// Frames are always clocked through `Pin::Data` and `Pin::Clock`,
+/// so every bit shows up in the trace.
+#[derive(Debug)]
+pub struct Recording<C: Connector, W: Write = std::io::BufWriter<std::fs::File>> {
+    /// Wrapped connector.
+    connector: C,
+
+    /// Trace output.
+    vcd: Vcd<W>,
+
+    /// Start of the recording.
+    start: Instant,
+
+    /// Register outputs of the wrapped connector, if available.
+    outputs: Option<Outputs<C>>,
+}
+
+impl<C: Connector, W: Write> Recording<C, W> {
+    /// Record pin changes of `connector` to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when writing the VCD header fails.
+    pub fn new(connector: C, writer: W) -> std::io::Result<Self> {
+        Ok(Self {
+            connector,
+            vcd: Vcd::new(writer, 0)?,
+            start: Instant::now(),
+            outputs: None,
+        })
+    }
+
+    /// Wrapped connector.
+    #[must_use]
+    pub const fn connector(&self) -> &C {
+        &self.connector
+    }
+
+    /// Flush the trace output.
+    ///
+    /// # Errors
+    ///
+    /// Errors when flushing fails.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.vcd.flush()
+    }
+
+    /// Unwrap the connector and trace output.
+    pub fn into_inner(self) -> (C, W) {
+        (self.connector, self.vcd.into_inner())
+    }
+}
+
+impl<C: Connector> Recording<C> {
+    /// Record pin changes of `connector` to a VCD file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the file can not be created or written.
+    pub fn create(connector: C, path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::File::create(path)?;
+        Self::new(connector, std::io::BufWriter::new(file))
+    }
+}
+
+#[cfg(feature = "emulator")]
+impl<W: Write> Recording<crate::Emulator, W> {
+    /// Record pin changes and register outputs of an `emulator` to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when writing the VCD header fails.
+    pub fn emulator(emulator: crate::Emulator, writer: W) -> std::io::Result<Self> {
+        Ok(Self {
+            vcd: Vcd::new(writer, emulator.registers().len())?,
+            connector: emulator,
+            start: Instant::now(),
+            outputs: Some(|emulator| emulator.registers().iter().map(|r| r.state()).collect()),
+        })
+    }
+}
+
+impl<C: Connector, W: Write> Connector for Recording<C, W> {
+    fn get(&self, pin: Pin) -> bool {
+        self.connector.get(pin)
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_get(&self, pin: Pin) -> Result<bool, Error> {
+        self.connector.try_get(pin)
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.connector.try_set(pin, state)?;
+
+        let at = self.start.elapsed();
+        self.vcd.pin(at, pin, state)?;
+
+        if let Some(outputs) = self.outputs {
+            self.vcd.registers(at, &outputs(&self.connector))?;
+        }
+
+        Ok(())
+    }
+
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        crate::clock_frame(self, data, len)
+    }
+
+    fn try_serial_out(&mut self) -> Result<bool, Error> {
+        self.connector.try_serial_out()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(identifier(0), "!");
+        assert_eq!(identifier(93), "~");
+        assert_eq!(identifier(94), "!!");
+        assert_eq!(identifier(95), "\"!");
+    }
+
+    #[test]
+    fn header_and_changes() {
+        let mut vcd = Vcd::new(Vec::new(), 2).expect("header");
+        vcd.pin(Duration::from_nanos(5), Pin::Data, true)
+            .expect("pin");
+        vcd.pin(Duration::from_nanos(5), Pin::Clock, true)
+            .expect("pin");
+        vcd.registers(Duration::from_nanos(9), &[0, 0b101])
+            .expect("registers");
+        vcd.registers(Duration::from_nanos(10), &[0, 0b101])
+            .expect("registers");
+
+        let trace = String::from_utf8(vcd.into_inner()).expect("utf8");
+        let body = trace
+            .split("$enddefinitions $end\n")
+            .nth(1)
+            .expect("definitions");
+
+        assert!(trace.contains("$var wire 1 # data $end"));
+        assert!(trace.contains("$var wire 8 ' register1 $end"));
+        assert!(body.ends_with("$end\n#5\n1#\n1!\n#9\nb101 '\n"));
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn emulator_outputs() {
+        let emulator = crate::Emulator::new(1);
+        let recording = Recording::emulator(emulator, Vec::new()).expect("header");
+        let mut controller = crate::Controller::connect(recording, 1);
+        controller.on();
+        controller.write(0b1011_0001_u8);
+
+        let (emulator, trace) = controller.connector.into_inner();
+        let trace = String::from_utf8(trace).expect("utf8");
+        let state = emulator.register(0).state();
+
+        assert_ne!(state, 0);
+        assert!(trace.contains("$var wire 8 & register0 $end"));
+        assert!(trace.ends_with(&format!("0$\nb{state:b} &\n")));
+        assert_eq!(trace.lines().filter(|line| *line == "1!").count(), 8);
+    }
+
+    #[test]
+    fn forwards_errors() {
+        /// Connector without any wired pins.
+        #[derive(Debug)]
+        struct Unwired;
+
+        impl Connector for Unwired {
+            fn set(&mut self, _pin: Pin, _state: bool) {}
+
+            fn get(&self, _pin: Pin) -> bool {
+                false
+            }
+
+            fn try_set(&mut self, pin: Pin, _state: bool) -> Result<(), Error> {
+                Err(Error::UnsupportedPin(pin))
+            }
+        }
+
+        let mut recording = Recording::new(Unwired, Vec::new()).expect("header");
+        assert!(matches!(
+            recording.try_set(Pin::Data, true),
+            Err(Error::UnsupportedPin(Pin::Data))
+        ));
+
+        let (_, trace) = recording.into_inner();
+        assert!(String::from_utf8(trace).expect("utf8").ends_with("$end\n"));
+    }
+}
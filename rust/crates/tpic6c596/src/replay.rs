@@ -0,0 +1,600 @@
+//! Replay recorded pin changes into a connector.
+//!
+//! Traces are read from a Value Change Dump (as written by `Recording`)
+//! or from a compact binary log.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{Connector, Error, Pin};
+
+/// Binary log magic, including the format version.
+const MAGIC: [u8; 8] = *b"TPICLOG1";
+
+/// A single recorded pin change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    /// Timestamp relative to the start of the trace.
+    pub at: Duration,
+
+    /// Changed pin.
+    pub pin: Pin,
+
+    /// New pin state.
+    pub state: bool,
+}
+
+/// Recorded pin changes, ordered by timestamp.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    /// Pin changes.
+    changes: Vec<Change>,
+}
+
+impl Trace {
+    /// Create an empty trace.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            changes: Vec::new(),
+        }
+    }
+
+    /// Add a pin change, timestamps should not decrease.
+    pub fn push(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    /// Recorded pin changes.
+    #[must_use]
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Total duration of the trace.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.changes
+            .last()
+            .map_or(Duration::ZERO, |change| change.at)
+    }
+
+    /// Read a trace from a file, either a binary log or a VCD.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the file can not be read or parsed.
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let data = std::fs::read(path)?;
+
+        if data.starts_with(&MAGIC) {
+            Self::read_log(data.as_slice())
+        } else {
+            Self::read_vcd(data.as_slice())
+        }
+    }
+
+    /// Read a trace from a Value Change Dump.
+    ///
+    /// Pins are matched on the variable names used by `Recording`:
+    /// `clock`, `control`, `data`, `latch`, and `clear`.
+    /// Other variables, like register outputs, are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Errors when reading fails or the dump is malformed.
+    pub fn read_vcd(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut tokens = text.split_whitespace();
+        let mut pins = HashMap::new();
+        let mut femtos = 1_000_000;
+        let mut time = 0;
+        let mut trace = Self::new();
+
+        while let Some(token) = tokens.next() {
+            match token {
+                "$timescale" => {
+                    let scale: String = tokens.by_ref().take_while(|t| *t != "$end").collect();
+                    femtos = timescale(&scale)?;
+                }
+                "$var" => {
+                    let var: Vec<_> = tokens.by_ref().take_while(|t| *t != "$end").collect();
+                    if let [_, "1", id, name, ..] = var.as_slice() {
+                        if let Some((pin, _)) = crate::recording::PINS
+                            .iter()
+                            .find(|(_, pin)| pin.eq_ignore_ascii_case(name))
+                        {
+                            pins.insert(*id, *pin);
+                        }
+                    }
+                }
+                "$dumpvars" | "$dumpall" | "$dumpon" | "$dumpoff" | "$end" => {}
+                keyword if keyword.starts_with('$') => {
+                    tokens.by_ref().find(|t| *t == "$end");
+                }
+                timestamp if timestamp.starts_with('#') => {
+                    time = timestamp[1..]
+                        .parse::<u128>()
+                        .map_err(|_| invalid("invalid VCD timestamp"))?;
+                }
+                vector if vector.starts_with(['b', 'B', 'r', 'R']) => {
+                    tokens.next();
+                }
+                scalar => {
+                    let mut chars = scalar.chars();
+                    let state = match chars.next() {
+                        Some('0') => false,
+                        Some('1') => true,
+                        Some('x' | 'X' | 'z' | 'Z') => continue,
+                        _ => return Err(invalid("invalid VCD value change")),
+                    };
+                    let id = chars.as_str();
+
+                    if let Some(&pin) = pins.get(id) {
+                        trace.push(Change {
+                            at: nanos(time * femtos / 1_000_000),
+                            pin,
+                            state,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(trace)
+    }
+
+    /// Read a trace from a binary log, see `write_log/1`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when reading fails or the log is malformed.
+    pub fn read_log(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut data = data
+            .strip_prefix(&MAGIC)
+            .ok_or_else(|| invalid("not a binary trace log"))?
+            .iter()
+            .copied();
+        let mut at = 0_u64;
+        let mut trace = Self::new();
+
+        while let Some(delta) = varint(&mut data)? {
+            let byte = data.next().ok_or_else(|| invalid("truncated trace log"))?;
+            let pin = match byte & 0b0111_1111 {
+                1 => Pin::Data,
+                2 => Pin::Control,
+                3 => Pin::Clock,
+                4 => Pin::Latch,
+                5 => Pin::Clear,
+                _ => return Err(invalid("invalid pin in trace log")),
+            };
+
+            at = at.saturating_add(delta);
+            trace.push(Change {
+                at: Duration::from_nanos(at),
+                pin,
+                state: byte & 0b1000_0000 != 0,
+            });
+        }
+
+        Ok(trace)
+    }
+
+    /// Write the trace as compact binary log.
+    ///
+    /// The log starts with a magic header, followed by one record per change:
+    /// the nanoseconds since the previous change as LEB128 varint
+    /// and a byte with the pin id and the state as most significant bit.
+    ///
+    /// # Errors
+    ///
+    /// Errors when writing fails.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_log(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let mut buffer = MAGIC.to_vec();
+        let mut previous = 0;
+
+        for change in &self.changes {
+            let at = u64::try_from(change.at.as_nanos()).unwrap_or(u64::MAX);
+            let mut delta = at.saturating_sub(previous);
+            previous = at;
+
+            while delta >= 0b1000_0000 {
+                buffer.push(0b1000_0000 | (delta & 0b0111_1111) as u8);
+                delta >>= 7;
+            }
+            buffer.push(delta as u8);
+
+            let state = if change.state { 0b1000_0000_u8 } else { 0 };
+            buffer.push(match change.pin {
+                Pin::Data => state | 1,
+                Pin::Control => state | 2,
+                Pin::Clock => state | 3,
+                Pin::Latch => state | 4,
+                Pin::Clear => state | 5,
+            });
+        }
+
+        writer.write_all(&buffer)
+    }
+}
+
+/// Invalid trace data error.
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// Duration from nanoseconds, saturating.
+fn nanos(nanos: u128) -> Duration {
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+/// Parse a VCD timescale (like `1ns` or `10 us`) into femtoseconds per tick.
+fn timescale(scale: &str) -> std::io::Result<u128> {
+    let split = scale
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(scale.len());
+    let (number, unit) = scale.split_at(split);
+
+    let number: u128 = number
+        .parse()
+        .map_err(|_| invalid("invalid VCD timescale"))?;
+    let unit: u128 = match unit {
+        "s" => 1_000_000_000_000_000,
+        "ms" => 1_000_000_000_000,
+        "us" => 1_000_000_000,
+        "ns" => 1_000_000,
+        "ps" => 1_000,
+        "fs" => 1,
+        _ => return Err(invalid("invalid VCD timescale unit")),
+    };
+
+    Ok(number * unit)
+}
+
+/// Read a LEB128 varint, `None` at the end of the data.
+fn varint(data: &mut impl Iterator<Item = u8>) -> std::io::Result<Option<u64>> {
+    let mut value = 0_u64;
+
+    for shift in (0..64).step_by(7) {
+        let Some(byte) = data.next() else {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err(invalid("truncated trace log"))
+            };
+        };
+
+        value |= u64::from(byte & 0b0111_1111) << shift;
+        if byte & 0b1000_0000 == 0 {
+            return Ok(Some(value));
+        }
+    }
+
+    Err(invalid("invalid varint in trace log"))
+}
+
+/// Replay timing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Apply changes as fast as possible.
+    Immediate,
+
+    /// Apply changes with the recorded timing.
+    Original,
+
+    /// Apply changes with the recorded timing multiplied by a factor, see `scaled/1`.
+    Scaled(Scale),
+}
+
+impl Pacing {
+    /// Apply changes with the recorded timing multiplied by `factor`,
+    /// for example `2.0` plays at half speed.
+    ///
+    /// # Errors
+    ///
+    /// Errors when `factor` is negative or not finite.
+    pub fn scaled(factor: f64) -> Result<Self, InvalidScale> {
+        if factor.is_finite() && factor >= 0.0 {
+            Ok(Self::Scaled(Scale(factor)))
+        } else {
+            Err(InvalidScale(factor))
+        }
+    }
+}
+
+/// Finite and not negative factor of `Pacing::Scaled`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale(f64);
+
+impl Scale {
+    /// Factor multiplying the recorded timing.
+    #[must_use]
+    pub const fn factor(self) -> f64 {
+        self.0
+    }
+}
+
+/// Negative or not finite `Pacing::Scaled` factor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidScale(pub f64);
+
+impl std::fmt::Display for InvalidScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "pacing factor {} must be finite and not negative",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidScale {}
+
+/// Replays a recorded trace into a connector.
+///
+/// Changes can be applied one at a time with `step/0`,
+/// for example to inspect emulator registers in between,
+/// or all at once with `run/1`.
+#[derive(Debug)]
+pub struct Replay<C: Connector> {
+    /// Connector receiving the changes.
+    connector: C,
+
+    /// Trace to replay.
+    trace: Trace,
+
+    /// Index of the next change.
+    next: usize,
+}
+
+impl<C: Connector> Replay<C> {
+    /// Replay `trace` into `connector`.
+    #[must_use]
+    pub const fn new(connector: C, trace: Trace) -> Self {
+        Self {
+            connector,
+            trace,
+            next: 0,
+        }
+    }
+
+    /// Connector receiving the changes.
+    #[must_use]
+    pub const fn connector(&self) -> &C {
+        &self.connector
+    }
+
+    /// Mutable connector receiving the changes.
+    #[must_use]
+    pub fn connector_mut(&mut self) -> &mut C {
+        &mut self.connector
+    }
+
+    /// Unwrap the connector.
+    pub fn into_inner(self) -> C {
+        self.connector
+    }
+
+    /// Next change to apply, `None` when finished.
+    #[must_use]
+    pub fn peek(&self) -> Option<Change> {
+        self.trace.changes.get(self.next).copied()
+    }
+
+    /// Number of applied changes.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.next
+    }
+
+    /// Restart from the beginning of the trace.
+    pub fn rewind(&mut self) {
+        self.next = 0;
+    }
+
+    /// Apply the next change, returning it or `None` when finished.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails, the change is not consumed.
+    pub fn step(&mut self) -> Result<Option<Change>, Error> {
+        let Some(change) = self.peek() else {
+            return Ok(None);
+        };
+
+        self.connector.try_set(change.pin, change.state)?;
+        self.next += 1;
+
+        Ok(Some(change))
+    }
+
+    /// Apply changes up to and including the next change of `pin` to `state`.
+    ///
+    /// For example `step_until(Pin::Latch, false)` steps frame by frame.
+    /// Returns `false` when the trace finished without such a change.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails.
+    pub fn step_until(&mut self, pin: Pin, state: bool) -> Result<bool, Error> {
+        while let Some(change) = self.step()? {
+            if change.pin == pin && change.state == state {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Apply all remaining changes, sleeping according to `pacing`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails.
+    pub fn run(&mut self, pacing: Pacing) -> Result<(), Error> {
+        let start = Instant::now();
+        let offset = self.peek().map_or(Duration::ZERO, |change| change.at);
+
+        while let Some(change) = self.peek() {
+            let elapsed = change.at.saturating_sub(offset);
+            let target = match pacing {
+                Pacing::Immediate => None,
+                Pacing::Original => Some(elapsed),
+                Pacing::Scaled(scale) => Some(elapsed.mul_f64(scale.factor())),
+            };
+
+            if let Some(wait) = target.and_then(|target| target.checked_sub(start.elapsed())) {
+                std::thread::sleep(wait);
+            }
+
+            self.step()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Changes of a short trace.
+    fn changes() -> Vec<Change> {
+        [
+            (0, Pin::Control, true),
+            (10, Pin::Data, true),
+            (200, Pin::Clock, true),
+            (129_000, Pin::Clock, false),
+            (129_000, Pin::Latch, true),
+            (5_000_000_000, Pin::Latch, false),
+        ]
+        .into_iter()
+        .map(|(at, pin, state)| Change {
+            at: Duration::from_nanos(at),
+            pin,
+            state,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn log_round_trip() {
+        let trace = Trace { changes: changes() };
+
+        let mut log = Vec::new();
+        trace.write_log(&mut log).expect("write");
+        assert!(log.len() < 8 + 6 * 6);
+
+        assert_eq!(Trace::read_log(log.as_slice()).expect("read"), trace);
+        assert!(Trace::read_log(&log[..log.len() - 1]).is_err());
+        assert!(Trace::read_log(&b"TPICLOG2"[..]).is_err());
+    }
+
+    #[test]
+    fn vcd() {
+        let vcd = "$timescale 10 us $end\n\
+                   $scope module column $end\n\
+                   $var wire 1 ! CLOCK $end\n\
+                   $var wire 1 \" data $end\n\
+                   $var wire 8 # register0 $end\n\
+                   $var wire 1 $ other $end\n\
+                   $upscope $end\n\
+                   $enddefinitions $end\n\
+                   #0\n$dumpvars\n0!\n0\"\nb0 #\nx$\n$end\n\
+                   #3\n1\"\n1$\n#4\n1!\nb1 #\n";
+
+        let trace = Trace::read_vcd(vcd.as_bytes()).expect("parse");
+        let changes: Vec<_> = trace
+            .changes()
+            .iter()
+            .map(|c| (c.at.as_micros(), c.pin, c.state))
+            .collect();
+
+        assert_eq!(
+            changes,
+            [
+                (0, Pin::Clock, false),
+                (0, Pin::Data, false),
+                (30, Pin::Data, true),
+                (40, Pin::Clock, true),
+            ]
+        );
+        assert!(Trace::read_vcd(&b"$timescale 1 xs $end"[..]).is_err());
+        assert!(Trace::read_vcd("#0\n\u{e9}!\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn pacing() {
+        let trace = Trace {
+            changes: changes()[..4].to_vec(),
+        };
+        let mut replay = Replay::new(Vec::new(), trace);
+
+        let start = Instant::now();
+        replay
+            .run(Pacing::scaled(20.0).expect("valid factor"))
+            .expect("run");
+        assert!(start.elapsed() >= Duration::from_micros(2_580));
+        assert_eq!(replay.position(), 4);
+        assert_eq!(replay.connector().len(), 4);
+
+        for factor in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(Pacing::scaled(factor).is_err());
+        }
+    }
+
+    /// Connector collecting pin changes.
+    impl Connector for Vec<(Pin, bool)> {
+        fn set(&mut self, pin: Pin, state: bool) {
+            self.push((pin, state));
+        }
+
+        fn get(&self, pin: Pin) -> bool {
+            self.iter()
+                .rev()
+                .find(|(p, _)| *p == pin)
+                .is_some_and(|c| c.1)
+        }
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn record_and_replay() {
+        use crate::{Controller, Emulator, Recording};
+
+        let recording = Recording::emulator(Emulator::new(2), Vec::new()).expect("header");
+        let mut controller = Controller::connect(recording, 2);
+        controller.on();
+        controller.write([0b1100_1010_u8, 0b0001_0111]);
+        controller.write([0b1111_0000_u8, 0b0000_0001]);
+
+        let (recorded, vcd) = controller.connector.into_inner();
+        let trace = Trace::read_vcd(vcd.as_slice()).expect("parse");
+
+        let mut reference = Controller::connect(Emulator::new(2), 2);
+        reference.on();
+        reference.write([0b1100_1010_u8, 0b0001_0111]);
+
+        let mut replay = Replay::new(Emulator::new(2), trace);
+        let mut frames = Vec::new();
+        while replay.step_until(Pin::Latch, false).expect("step") {
+            frames.push(replay.connector().registers().to_vec());
+        }
+
+        assert!(frames.contains(&reference.connector().registers().to_vec()));
+        assert_eq!(replay.peek(), None);
+
+        let replayed = replay.into_inner();
+        assert_eq!(replayed.registers(), recorded.registers());
+        assert!(replayed.is_on());
+    }
+}
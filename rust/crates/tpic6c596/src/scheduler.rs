@@ -0,0 +1,332 @@
+//! Fixed-rate, double-buffered frame output.
+//!
+//! Producers submit frames into a back buffer whenever they like,
+//! while a dedicated thread writes the front buffer at a fixed rate.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{Bits, Connector, Controller};
+
+/// Frame scheduler statistics.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Refreshes written to the chain.
+    pub refreshes: u64,
+
+    /// Submitted frames that were shown.
+    pub frames: u64,
+
+    /// Submitted frames replaced by a newer frame before being shown.
+    pub dropped: u64,
+
+    /// Refreshes skipped because the scheduler fell behind.
+    pub missed: u64,
+
+    /// Average deviation of refreshes from their scheduled time.
+    pub mean_jitter: Duration,
+
+    /// Largest deviation of a refresh from its scheduled time.
+    pub max_jitter: Duration,
+}
+
+/// Back buffer shared with producers.
+#[derive(Debug)]
+struct Back {
+    /// Frame bytes, one bit per light.
+    frame: Vec<u8>,
+
+    /// Whether `frame` was submitted but not yet shown.
+    pending: bool,
+}
+
+/// State shared between the scheduler and its thread.
+#[derive(Debug)]
+struct Shared {
+    /// Back buffer.
+    back: Mutex<Back>,
+
+    /// Signalled when the back buffer is swapped to the front.
+    swapped: Condvar,
+
+    /// Statistics.
+    stats: Mutex<Measurements>,
+}
+
+/// Statistics and running totals.
+#[derive(Debug, Default)]
+struct Measurements {
+    /// Statistics.
+    stats: FrameStats,
+
+    /// Total jitter, to compute the mean.
+    jitter: Duration,
+}
+
+/// Fixed-rate, double-buffered frame scheduler.
+///
+/// Owns the controller and refreshes the chain every `period` on a dedicated thread.
+/// Frames submitted using `submit/1` are shown from the next refresh on,
+/// frames replaced before they were shown are counted as dropped.
+#[derive(Debug)]
+pub struct FrameScheduler<C: Connector + Send + 'static> {
+    /// State shared with the refresh thread.
+    shared: Arc<Shared>,
+
+    /// Frame length in lights, see `Controller::lights/0`.
+    bits: usize,
+
+    /// Stop signal for the refresh thread.
+    stop: Arc<AtomicBool>,
+
+    /// Refresh thread, returning the controller when stopped.
+    thread: Option<JoinHandle<Controller<C>>>,
+}
+
+impl<C: Connector + Send + 'static> FrameScheduler<C> {
+    /// Start refreshing the chain of `controller` every `period`,
+    /// for example `Duration::from_secs(1) / 60` for 60 frames per second.
+    ///
+    /// The chain shows an empty frame until the first frame is submitted.
+    #[must_use]
+    pub fn start(mut controller: Controller<C>, period: Duration) -> Self {
+        let bits = controller.lights();
+        let shared = Arc::new(Shared {
+            back: Mutex::new(Back {
+                frame: vec![0; bits.div_ceil(8)],
+                pending: false,
+            }),
+            swapped: Condvar::new(),
+            stats: Mutex::default(),
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let stop = Arc::clone(&stop);
+
+            std::thread::spawn(move || {
+                let mut front = vec![0; bits.div_ceil(8)];
+                let mut deadline = Instant::now();
+
+                while !stop.load(Ordering::Relaxed) {
+                    let jitter = Instant::now().saturating_duration_since(deadline);
+                    shared.swap(&mut front);
+                    controller.write(front.as_slice());
+                    deadline += period;
+
+                    let mut missed = 0;
+                    while deadline < Instant::now() && !period.is_zero() {
+                        deadline += period;
+                        missed += 1;
+                    }
+
+                    shared.record(jitter, missed);
+                    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                }
+
+                controller
+            })
+        };
+
+        Self {
+            shared,
+            bits,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Submit a frame to show from the next refresh on.
+    ///
+    /// Replaces (and drops) a previously submitted frame that was not shown yet.
+    pub fn submit(&self, frame: impl Bits) {
+        let mut back = lock(&self.shared.back);
+
+        back.frame.fill(0);
+        for index in (0..self.bits).filter(|index| frame.bit(*index)) {
+            back.frame[index / 8] |= 1 << (index % 8);
+        }
+
+        if back.pending {
+            lock(&self.shared.stats).stats.dropped += 1;
+        }
+        back.pending = true;
+    }
+
+    /// Wait until the last submitted frame is shown.
+    ///
+    /// Returns immediately when no frame is pending.
+    pub fn wait(&self) {
+        let back = lock(&self.shared.back);
+
+        drop(
+            self.shared
+                .swapped
+                .wait_while(back, |back| back.pending)
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+    }
+
+    /// Submit a frame and wait until it is shown.
+    ///
+    /// Paces a producer to the refresh rate without dropping frames.
+    pub fn present(&self, frame: impl Bits) {
+        self.submit(frame);
+        self.wait();
+    }
+
+    /// Statistics since start or the last `reset_stats/0`.
+    #[must_use]
+    pub fn stats(&self) -> FrameStats {
+        lock(&self.shared.stats).stats
+    }
+
+    /// Reset the statistics.
+    pub fn reset_stats(&self) {
+        *lock(&self.shared.stats) = Measurements::default();
+    }
+
+    /// Stop refreshing and return the controller.
+    ///
+    /// The chain keeps showing the last written frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the refresh thread panicked.
+    #[must_use]
+    pub fn stop(mut self) -> Controller<C> {
+        self.join().expect("scheduler thread running")
+    }
+
+    /// Signal the refresh thread to stop and wait for it.
+    fn join(&mut self) -> Option<Controller<C>> {
+        self.stop.store(true, Ordering::Relaxed);
+
+        self.thread
+            .take()
+            .map(|thread| thread.join().expect("scheduler thread not to panic"))
+    }
+}
+
+impl<C: Connector + Send + 'static> Drop for FrameScheduler<C> {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+impl Shared {
+    /// Swap a pending back buffer to the front.
+    fn swap(&self, front: &mut [u8]) {
+        let mut back = lock(&self.back);
+
+        if back.pending {
+            front.copy_from_slice(&back.frame);
+            back.pending = false;
+            lock(&self.stats).stats.frames += 1;
+        }
+
+        drop(back);
+        self.swapped.notify_all();
+    }
+
+    /// Record a refresh in the statistics.
+    fn record(&self, jitter: Duration, missed: u64) {
+        let mut measurements = lock(&self.stats);
+        measurements.jitter += jitter;
+
+        let total = measurements.jitter;
+        let stats = &mut measurements.stats;
+        stats.refreshes += 1;
+        stats.missed += missed;
+        stats.max_jitter = stats.max_jitter.max(jitter);
+        stats.mean_jitter = total / u32::try_from(stats.refreshes).unwrap_or(u32::MAX);
+        drop(measurements);
+    }
+}
+
+/// Lock a mutex, ignoring poisoning.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(all(test, feature = "emulator"))]
+mod tests {
+    use super::*;
+    use crate::Emulator;
+
+    /// Start a scheduler on an emulated chain.
+    fn scheduler(chain: usize, period: Duration) -> FrameScheduler<Emulator> {
+        let mut controller = Controller::connect(Emulator::new(chain), chain);
+        controller.on();
+
+        FrameScheduler::start(controller, period)
+    }
+
+    #[test]
+    fn present() {
+        let scheduler = scheduler(2, Duration::from_millis(2));
+
+        for frame in 0..10_u16 {
+            scheduler.present(frame * 0x0101);
+        }
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.frames, 10);
+        assert_eq!(stats.dropped, 0);
+        assert!(stats.refreshes >= 9);
+        assert!(stats.max_jitter >= stats.mean_jitter);
+
+        let mut reference = Controller::connect(Emulator::new(2), 2);
+        reference.on();
+        reference.write(9 * 0x0101_u16);
+
+        let controller = scheduler.stop();
+        assert_eq!(
+            controller.connector().registers(),
+            reference.connector().registers()
+        );
+    }
+
+    #[test]
+    fn layout() {
+        let layout = crate::Layout::new([(1, 0), (0, 7), (0, 0)]).expect("layout");
+        let mut controller = Controller::connect(Emulator::new(2), 2).with_layout(layout);
+        controller.on();
+
+        let scheduler = FrameScheduler::start(controller, Duration::from_millis(2));
+        scheduler.present(0b110_u8);
+
+        let controller = scheduler.stop();
+        assert_eq!(controller.connector().register(0).state(), 0b1000_0001);
+        assert_eq!(controller.connector().register(1).state(), 0);
+    }
+
+    #[test]
+    fn dropped_frames() {
+        let scheduler = scheduler(1, Duration::from_millis(50));
+        std::thread::sleep(Duration::from_millis(5));
+
+        scheduler.submit(0b0000_0001_u8);
+        scheduler.submit(0b0000_0011_u8);
+        scheduler.submit(0b0000_0111_u8);
+        scheduler.wait();
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.frames, 1);
+
+        scheduler.reset_stats();
+        assert_eq!(scheduler.stats(), FrameStats::default());
+        assert_eq!(
+            scheduler.stop().connector().register(0).state(),
+            0b1110_0000
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Fan-out connector mirroring pin changes to several targets.
+//!
+//! For example to show the same frames on the real chain and the emulator
+//! while commissioning.
+
+use crate::{Bits, Connector, Controller, Error, Pin};
+
+/// Behaviour when a `Tee` target fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Failure {
+    /// Report the error, after forwarding the change to the other targets.
+    #[default]
+    Propagate,
+
+    /// Ignore the error and keep forwarding to the target.
+    Ignore,
+
+    /// Stop forwarding to the target after its first error.
+    Detach,
+}
+
+/// Connector target of a `Tee`.
+struct Target {
+    /// Connector.
+    connector: Box<dyn Connector + Send>,
+
+    /// Behaviour when the connector fails.
+    failure: Failure,
+
+    /// Whether the connector was detached after failing.
+    detached: bool,
+}
+
+/// Connector forwarding every pin change to several connectors.
+///
+/// Pin states are read from the first (primary) target.
+/// Batches and frames are forwarded as such, so every target can use its own fast path.
+pub struct Tee {
+    /// Targets, the primary first.
+    targets: Vec<Target>,
+}
+
+impl Tee {
+    /// Forward to `primary`, reporting its errors.
+    #[must_use]
+    pub fn new(primary: impl Connector + Send + 'static) -> Self {
+        Self {
+            targets: Vec::new(),
+        }
+        .with_target(primary, Failure::Propagate)
+    }
+
+    /// Also forward to `target`, handling its errors using `failure`.
+    #[must_use]
+    pub fn with_target(
+        mut self,
+        target: impl Connector + Send + 'static,
+        failure: Failure,
+    ) -> Self {
+        self.targets.push(Target {
+            connector: Box::new(target),
+            failure,
+            detached: false,
+        });
+        self
+    }
+
+    /// Number of targets, including detached targets.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Whether the tee has no targets, never the case.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Whether the target at `index` was detached after failing.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of range.
+    #[must_use]
+    pub fn is_detached(&self, index: usize) -> bool {
+        self.targets[index].detached
+    }
+
+    /// Run `operation` on every attached target, in order.
+    ///
+    /// Returns the first error of a target with `Failure::Propagate`.
+    fn forward(
+        &mut self,
+        mut operation: impl FnMut(&mut dyn Connector) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut result = Ok(());
+
+        for target in self.targets.iter_mut().filter(|target| !target.detached) {
+            if let Err(error) = operation(target.connector.as_mut()) {
+                match target.failure {
+                    Failure::Propagate if result.is_ok() => result = Err(error),
+                    Failure::Propagate | Failure::Ignore => {}
+                    Failure::Detach => target.detached = true,
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Primary target.
+    fn primary(&self) -> &dyn Connector {
+        self.targets[0].connector.as_ref()
+    }
+}
+
+impl core::fmt::Debug for Tee {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries(
+                self.targets
+                    .iter()
+                    .map(|target| (target.failure, target.detached)),
+            )
+            .finish()
+    }
+}
+
+impl Connector for Tee {
+    fn get(&self, pin: Pin) -> bool {
+        self.primary().get(pin)
+    }
+
+    fn set(&mut self, pin: Pin, state: bool) {
+        let _ = self.try_set(pin, state);
+    }
+
+    fn try_get(&self, pin: Pin) -> Result<bool, Error> {
+        self.primary().try_get(pin)
+    }
+
+    fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+        self.forward(|connector| connector.try_set(pin, state))
+    }
+
+    fn try_set_batch(&mut self, changes: &[(Pin, bool)]) -> Result<(), Error> {
+        self.forward(|connector| connector.try_set_batch(changes))
+    }
+
+    fn try_set_timed(
+        &mut self,
+        changes: &[(Pin, bool)],
+        at: &[std::time::Instant],
+    ) -> Result<(), Error> {
+        self.forward(|connector| connector.try_set_timed(changes, at))
+    }
+
+    fn try_shift_frame(&mut self, data: &dyn Bits, len: usize) -> Result<(), Error> {
+        self.forward(|connector| connector.try_shift_frame(data, len))
+    }
+
+    fn try_serial_out(&mut self) -> Result<bool, Error> {
+        self.targets[0].connector.try_serial_out()
+    }
+}
+
+impl<C: Connector + Send + 'static> Controller<C> {
+    /// Mirror every pin change to `target`, see `Tee`.
+    ///
+    /// Keeps the controller settings and state, errors of the current connector are reported.
+    #[must_use]
+    pub fn mirrored(
+        self,
+        target: impl Connector + Send + 'static,
+        failure: Failure,
+    ) -> Controller<Tee> {
+        self.map_connector(|connector| Tee::new(connector).with_target(target, failure))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connector failing after a number of pin changes, recording the successful ones.
+    #[derive(Debug)]
+    struct Flaky(usize, std::sync::Arc<std::sync::Mutex<Vec<(Pin, bool)>>>);
+
+    impl Connector for Flaky {
+        fn set(&mut self, _pin: Pin, _state: bool) {}
+
+        fn get(&self, _pin: Pin) -> bool {
+            false
+        }
+
+        fn try_set(&mut self, pin: Pin, state: bool) -> Result<(), Error> {
+            if self.0 == 0 {
+                return Err(Error::Disconnected);
+            }
+
+            self.0 -= 1;
+            self.1.lock().expect("changes").push((pin, state));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failures() {
+        let changes = || std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (primary, ignored, detached) = (changes(), changes(), changes());
+
+        let mut tee = Tee::new(Flaky(usize::MAX, primary.clone()))
+            .with_target(Flaky(1, ignored.clone()), Failure::Ignore)
+            .with_target(Flaky(1, detached.clone()), Failure::Detach);
+
+        assert!(tee.try_set(Pin::Data, true).is_ok());
+        assert!(tee.try_set(Pin::Clock, true).is_ok());
+        assert!(tee.is_detached(2));
+        assert!(!tee.is_detached(1));
+        assert_eq!(tee.len(), 3);
+
+        assert_eq!(primary.lock().expect("primary").len(), 2);
+        assert_eq!(*ignored.lock().expect("ignored"), [(Pin::Data, true)]);
+        assert_eq!(*detached.lock().expect("detached"), [(Pin::Data, true)]);
+
+        // Propagated errors are reported after forwarding to the other targets.
+        let mut tee = Tee::new(Flaky(0, changes()))
+            .with_target(Flaky(1, primary.clone()), Failure::Propagate);
+        assert!(matches!(
+            tee.try_set(Pin::Latch, true),
+            Err(Error::Disconnected)
+        ));
+        assert_eq!(
+            primary.lock().expect("primary").last(),
+            Some(&(Pin::Latch, true))
+        );
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn mirror() {
+        use crate::Emulator;
+
+        /// Shares an emulator, to inspect it while mirrored.
+        #[derive(Debug, Clone)]
+        struct Shared(std::sync::Arc<std::sync::Mutex<Emulator>>);
+
+        impl Connector for Shared {
+            fn set(&mut self, pin: Pin, state: bool) {
+                self.0.lock().expect("emulator").set_pin(pin, state);
+            }
+
+            fn get(&self, pin: Pin) -> bool {
+                self.0.lock().expect("emulator").get_pin(pin)
+            }
+        }
+
+        let mirror = Shared(std::sync::Arc::new(std::sync::Mutex::new(Emulator::new(2))));
+        let mut controller = Controller::connect(Emulator::new(2), 2)
+            .batched()
+            .mirrored(mirror.clone(), Failure::Detach);
+        controller.on();
+        controller.write(0x1234_u16);
+
+        let mut reference = Controller::connect(Emulator::new(2), 2);
+        reference.on();
+        reference.write(0x1234_u16);
+
+        let emulator = mirror.0.lock().expect("emulator");
+        assert!(emulator.is_on());
+        assert_eq!(emulator.registers(), reference.connector().registers());
+        drop(emulator);
+        assert!(controller.connector().get(Pin::Control));
+    }
+}
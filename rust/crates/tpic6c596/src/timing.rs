@@ -0,0 +1,211 @@
+//! Timing checks for the emulator
+
+use std::time::Duration;
+
+use crate::{Pin, Pins};
+
+/// Timing requirements of the TPIC6C596 pins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// Minimum time `Pin::Data` has to be stable before the rising edge of `Pin::Clock`.
+    pub setup: Duration,
+
+    /// Minimum time `Pin::Data` has to be stable after the rising edge of `Pin::Clock`.
+    pub hold: Duration,
+
+    /// Minimum high or low pulse duration of `Pin::Clock`, `Pin::Latch`, and `Pin::Clear`.
+    pub pulse_width: Duration,
+}
+
+impl Timing {
+    /// Timing requirements from the TPIC6C596 datasheet,
+    /// at a supply voltage of 5 V and 25 °C.
+    pub const DATASHEET: Self = Self {
+        setup: Duration::from_nanos(15),
+        hold: Duration::from_nanos(15),
+        pulse_width: Duration::from_nanos(40),
+    };
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self::DATASHEET
+    }
+}
+
+/// Kind of timing violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// Data changed too close before the clock's rising edge.
+    Setup,
+
+    /// Data changed too close after the clock's rising edge.
+    Hold,
+
+    /// A clock, latch, or clear pulse was too short.
+    PulseWidth,
+}
+
+/// A timing violation detected by the emulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    /// Kind of violation.
+    pub kind: ViolationKind,
+
+    /// Pin whose change caused the violation.
+    pub pin: Pin,
+
+    /// Timestamp of the offending pin change.
+    pub at: Duration,
+
+    /// Measured duration.
+    pub actual: Duration,
+
+    /// Required minimum duration.
+    pub required: Duration,
+}
+
+/// Tracks timestamped pin changes and collects violations.
+#[derive(Debug)]
+pub struct Checker {
+    /// Timing requirements.
+    timing: Timing,
+
+    /// Timestamp of the last change per pin.
+    changed: Pins<Option<Duration>>,
+
+    /// Timestamp of the last rising clock edge.
+    clock_rise: Option<Duration>,
+
+    /// Collected violations.
+    violations: Vec<Violation>,
+}
+
+impl Checker {
+    /// Create a checker for the given timing requirements.
+    pub fn new(timing: Timing) -> Self {
+        Self {
+            timing,
+            changed: Pins::default(),
+            clock_rise: None,
+            violations: Vec::new(),
+        }
+    }
+
+    /// Timing requirements.
+    pub const fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Check a pin change to `state` at `at`.
+    pub fn check(&mut self, pin: Pin, state: bool, at: Duration) {
+        match pin {
+            Pin::Data => {
+                if let Some(rise) = self.clock_rise {
+                    self.require(ViolationKind::Hold, pin, at, rise, self.timing.hold);
+                }
+            }
+            Pin::Clock | Pin::Latch | Pin::Clear => {
+                if let Some(previous) = self.changed.get(pin) {
+                    let width = self.timing.pulse_width;
+                    self.require(ViolationKind::PulseWidth, pin, at, previous, width);
+                }
+
+                if pin == Pin::Clock && state {
+                    if let Some(data) = self.changed.get(Pin::Data) {
+                        self.require(ViolationKind::Setup, pin, at, data, self.timing.setup);
+                    }
+                    self.clock_rise = Some(at);
+                }
+            }
+            Pin::Control => {}
+        }
+
+        self.changed.set(pin, Some(at));
+    }
+
+    /// Record a violation when less than `required` passed between `since` and `at`.
+    fn require(
+        &mut self,
+        kind: ViolationKind,
+        pin: Pin,
+        at: Duration,
+        since: Duration,
+        required: Duration,
+    ) {
+        let actual = at.saturating_sub(since);
+
+        if actual < required {
+            self.violations.push(Violation {
+                kind,
+                pin,
+                at,
+                actual,
+                required,
+            });
+        }
+    }
+
+    /// Collected violations.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Take collected violations, clearing the list.
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        std::mem::take(&mut self.violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nanoseconds shorthand.
+    const fn ns(nanos: u64) -> Duration {
+        Duration::from_nanos(nanos)
+    }
+
+    #[test]
+    fn within_spec() {
+        let mut checker = Checker::new(Timing::DATASHEET);
+
+        checker.check(Pin::Data, true, ns(0));
+        checker.check(Pin::Clock, true, ns(50));
+        checker.check(Pin::Clock, false, ns(100));
+        checker.check(Pin::Data, false, ns(100));
+        checker.check(Pin::Clock, true, ns(150));
+
+        assert!(checker.violations().is_empty());
+    }
+
+    #[test]
+    fn setup_and_hold() {
+        let mut checker = Checker::new(Timing::DATASHEET);
+
+        checker.check(Pin::Data, true, ns(0));
+        checker.check(Pin::Clock, true, ns(10));
+        checker.check(Pin::Data, false, ns(20));
+
+        let kinds: Vec<_> = checker.violations().iter().map(|v| v.kind).collect();
+        assert_eq!(kinds, [ViolationKind::Setup, ViolationKind::Hold]);
+        assert_eq!(checker.violations()[0].actual, ns(10));
+        assert_eq!(checker.violations()[1].actual, ns(10));
+    }
+
+    #[test]
+    fn pulse_width() {
+        let mut checker = Checker::new(Timing::DATASHEET);
+
+        checker.check(Pin::Latch, true, ns(0));
+        checker.check(Pin::Latch, false, ns(39));
+        checker.check(Pin::Latch, true, ns(79));
+
+        let violations = checker.take_violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, ViolationKind::PulseWidth);
+        assert_eq!(violations[0].pin, Pin::Latch);
+        assert_eq!(violations[0].required, ns(40));
+        assert!(checker.violations().is_empty());
+    }
+}
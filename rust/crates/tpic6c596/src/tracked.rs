@@ -0,0 +1,182 @@
+//! Tracked light state with incremental changes.
+//!
+//! The controller remembers the last written frame, so single lights can be
+//! changed and committed without the caller keeping a copy.
+
+use crate::{Bits, Connector, Controller, Error, Frame};
+
+/// Written and pending lights of a `Controller`.
+#[derive(Debug, Clone)]
+pub struct Tracking {
+    /// Lights including uncommitted changes.
+    lights: Frame,
+
+    /// Lights last written to the chain, `None` when unknown.
+    latched: Option<Frame>,
+}
+
+impl Tracking {
+    /// Track `len` lights, all off and not yet written.
+    pub fn new(len: usize) -> Self {
+        Self {
+            lights: Frame::new(len),
+            latched: None,
+        }
+    }
+
+    /// Forget the latched lights, after shifting something else than a frame.
+    pub fn invalidate(&mut self) {
+        self.latched = None;
+    }
+
+    /// Record a written frame, discarding uncommitted changes.
+    pub fn written(&mut self, data: &dyn Bits) {
+        for light in 0..self.lights.len() {
+            self.lights.set(light, data.bit(light));
+        }
+
+        match &mut self.latched {
+            Some(latched) => latched.clone_from(&self.lights),
+            None => self.latched = Some(self.lights.clone()),
+        }
+    }
+}
+
+impl<C: Connector> Controller<C> {
+    /// Lights including changes not yet committed, see `commit/0`.
+    ///
+    /// Lights follow the frames passed to `write/1`, in logical order with a layout.
+    /// All lights are off before the first write.
+    #[must_use]
+    pub const fn state(&self) -> &Frame {
+        &self.tracking.lights
+    }
+
+    /// Lights last written to the chain.
+    ///
+    /// `None` before the first write and after shifting raw bits using `shift/2`.
+    #[must_use]
+    pub const fn latched(&self) -> Option<&Frame> {
+        self.tracking.latched.as_ref()
+    }
+
+    /// Whether the lights have changes not yet committed.
+    #[must_use]
+    pub fn has_changes(&self) -> bool {
+        self.tracking.latched.as_ref() != Some(&self.tracking.lights)
+    }
+
+    /// Turn a light on or off, without writing it yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `light` is out of range, see `state/0`.
+    pub fn set_light(&mut self, light: usize, on: bool) {
+        self.tracking.lights.set(light, on);
+    }
+
+    /// Toggle a light without writing it yet, returning whether it is on now.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `light` is out of range, see `state/0`.
+    pub fn toggle(&mut self, light: usize) -> bool {
+        self.tracking.lights.toggle(light)
+    }
+
+    /// Write the lights when they changed since the last write.
+    ///
+    /// Connector errors are ignored, see `try_commit/0`.
+    pub fn commit(&mut self) {
+        let _ = self.try_commit();
+    }
+
+    /// Try to write the lights when they changed since the last write.
+    ///
+    /// Returns whether the lights were written.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    pub fn try_commit(&mut self) -> Result<bool, Error> {
+        if !self.has_changes() {
+            return Ok(false);
+        }
+
+        let lights = self.tracking.lights.clone();
+        self.try_write(&lights)?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(all(test, feature = "emulator"))]
+mod tests {
+    use super::*;
+    use crate::{Emulator, Layout, Pin};
+
+    /// Connector counting latches.
+    #[derive(Debug)]
+    struct Latches(Emulator, usize);
+
+    impl Connector for Latches {
+        fn set(&mut self, pin: Pin, state: bool) {
+            if pin == Pin::Latch && state {
+                self.1 += 1;
+            }
+            self.0.set_pin(pin, state);
+        }
+
+        fn get(&self, pin: Pin) -> bool {
+            self.0.get_pin(pin)
+        }
+    }
+
+    #[test]
+    fn commit_changes() {
+        let mut controller = Controller::connect(Latches(Emulator::new(2), 0), 2);
+        controller.on();
+        assert_eq!(controller.state().len(), 16);
+        assert!(controller.latched().is_none());
+
+        controller.set_light(0, true);
+        assert!(controller.toggle(9));
+        assert!(controller.has_changes());
+        assert!(controller.try_commit().expect("commit"));
+        assert!(!controller.try_commit().expect("commit"));
+        assert_eq!(controller.connector().1, 1);
+
+        let frame = controller.connector().0.frame();
+        assert_eq!(frame.to_string(), "#........#......");
+        assert_eq!(controller.latched(), Some(&frame));
+
+        // Toggling back and forth is no change.
+        controller.toggle(3);
+        controller.toggle(3);
+        controller.commit();
+        assert_eq!(controller.connector().1, 1);
+
+        // Writes replace the lights, raw shifts forget them.
+        controller.write(0b10_u8);
+        assert_eq!(controller.state().to_string(), ".#..............");
+        controller.shift_high();
+        assert!(controller.latched().is_none());
+        controller.commit();
+        assert_eq!(controller.connector().1, 4);
+        assert_eq!(controller.connector().0.frame(), *controller.state());
+    }
+
+    #[test]
+    fn layout_lights() {
+        let layout = Layout::new([(0, 0), (1, 7), (1, 0)]).expect("layout");
+        let mut controller = Controller::connect(Emulator::new(2), 2).with_layout(layout);
+        controller.on();
+        assert_eq!(controller.state().len(), 3);
+
+        controller.set_light(1, true);
+        controller.set_light(2, true);
+        controller.commit();
+        assert_eq!(controller.connector().register(1).state(), 0b1000_0001);
+        assert_eq!(controller.connector().register(0).state(), 0);
+    }
+}
@@ -0,0 +1,621 @@
+//! Safety watchdog blanking the chain when frames stop.
+//!
+//! A hanging or crashed producer leaves the chain frozen in its last state,
+//! possibly with all lights on. The watchdog puts the chain in a safe state
+//! when no frame arrives within a deadline, and leaves it as soon as frames resume.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{Bits, Command, Connector, Controller, ControllerHandle, Error, Priority};
+
+/// Monotonic time source for the `Watchdog`.
+///
+/// Implemented by `Monotonic`, but can be implemented to inject a mocked clock.
+pub trait TimeSource {
+    /// Time elapsed since an arbitrary, fixed starting point.
+    #[must_use]
+    fn now(&self) -> Duration;
+}
+
+/// Time source using `std::time::Instant`.
+#[derive(Debug, Clone, Copy)]
+pub struct Monotonic(Instant);
+
+impl Default for Monotonic {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl TimeSource for Monotonic {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// Safe state applied by the `Watchdog` when the deadline passes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fallback {
+    /// Turn the outputs off using `Pin::Control`, keeping the latched frame.
+    Off,
+
+    /// Write a frame, in logical light order when the controller has a `Layout`.
+    Frame(Vec<u8>),
+}
+
+/// Lights supervised by a `Watchdog`.
+///
+/// Implemented by `Controller` and by `ControllerHandle`, to supervise a chain
+/// shared through a `ControllerThread`.
+pub trait Supervised {
+    /// Try to write a frame, see `Controller::try_write`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the frame could not be written.
+    fn try_write(&mut self, data: &dyn Bits) -> Result<(), Error>;
+
+    /// Try to turn the outputs on, see `Controller::try_on`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the outputs could not be turned on.
+    fn try_on(&mut self) -> Result<(), Error>;
+
+    /// Whether the outputs are on.
+    #[must_use]
+    fn is_on(&self) -> bool;
+
+    /// Try to apply the safe state.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the safe state could not be applied.
+    fn try_fallback(&mut self, fallback: &Fallback) -> Result<(), Error>;
+}
+
+impl<C: Connector> Supervised for Controller<C> {
+    fn try_write(&mut self, data: &dyn Bits) -> Result<(), Error> {
+        Self::try_write(self, data)
+    }
+
+    fn try_on(&mut self) -> Result<(), Error> {
+        Self::try_on(self)
+    }
+
+    fn is_on(&self) -> bool {
+        self.on
+    }
+
+    fn try_fallback(&mut self, fallback: &Fallback) -> Result<(), Error> {
+        match fallback {
+            Fallback::Off => self.try_off(),
+            Fallback::Frame(frame) => Self::try_write(self, frame.as_slice()),
+        }
+    }
+}
+
+/// Frames and recovery are queued, the fallback runs with `Priority::Safety`
+/// and waits until it ran.
+impl Supervised for ControllerHandle {
+    fn try_write(&mut self, data: &dyn Bits) -> Result<(), Error> {
+        self.write(data)
+    }
+
+    fn try_on(&mut self) -> Result<(), Error> {
+        self.on()
+    }
+
+    fn is_on(&self) -> bool {
+        Self::is_on(self)
+    }
+
+    fn try_fallback(&mut self, fallback: &Fallback) -> Result<(), Error> {
+        let command = match fallback {
+            Fallback::Off => Command::Off,
+            Fallback::Frame(frame) => Command::Write(frame.clone()),
+        };
+
+        self.execute(command, Priority::Safety)
+    }
+}
+
+/// Event reported by the `Watchdog`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// The deadline passed and the fallback was applied.
+    Expired {
+        /// Time since the last frame.
+        silence: Duration,
+    },
+
+    /// Frames resumed after the deadline passed.
+    Recovered,
+}
+
+/// Hook receiving watchdog events.
+type Hook = Box<dyn FnMut(WatchdogEvent) + Send>;
+
+/// Safety watchdog on top of a `Controller` or `ControllerHandle`, see `Supervised`.
+///
+/// Frames are written using `write/1`. When `check/0` finds no frame was written
+/// within the deadline, the `Fallback` is applied once until frames resume.
+/// Use `start/1` to check on a dedicated thread.
+pub struct Watchdog<S: Supervised, T: TimeSource = Monotonic> {
+    /// Supervised controller.
+    controller: S,
+
+    /// Time source.
+    time: T,
+
+    /// Longest time between frames.
+    deadline: Duration,
+
+    /// Safe state.
+    fallback: Fallback,
+
+    /// Event hook.
+    hook: Option<Hook>,
+
+    // Local State
+    /// Time of the last frame.
+    last: Duration,
+
+    /// Whether the fallback is applied.
+    expired: bool,
+
+    /// Whether to turn the outputs back on when frames resume.
+    resume: bool,
+}
+
+impl<S: Supervised> Watchdog<S> {
+    /// Supervise `controller`, turning its outputs off when no frame
+    /// is written within `deadline`.
+    #[must_use]
+    pub fn new(controller: S, deadline: Duration) -> Self {
+        Self::with_time_source(controller, deadline, Monotonic::default())
+    }
+}
+
+impl<S: Supervised, T: TimeSource> Watchdog<S, T> {
+    /// Supervise `controller` using a specific time source, see `new/2`.
+    #[must_use]
+    pub fn with_time_source(controller: S, deadline: Duration, time: T) -> Self {
+        Self {
+            last: time.now(),
+            controller,
+            time,
+            deadline,
+            fallback: Fallback::Off,
+            hook: None,
+            expired: false,
+            resume: false,
+        }
+    }
+
+    /// Safe state to apply when the deadline passes, `Fallback::Off` by default.
+    #[must_use]
+    pub fn with_fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Report watchdog events to `hook`.
+    #[must_use]
+    pub fn on_event(mut self, hook: impl FnMut(WatchdogEvent) + Send + 'static) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Supervised controller.
+    #[must_use]
+    pub const fn controller(&self) -> &S {
+        &self.controller
+    }
+
+    /// Longest time between frames.
+    #[must_use]
+    pub const fn deadline(&self) -> Duration {
+        self.deadline
+    }
+
+    /// Whether the deadline passed and no frame was written since.
+    #[must_use]
+    pub const fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Write a frame and restart the deadline.
+    ///
+    /// Connector errors are ignored, see `try_write/1`.
+    pub fn write(&mut self, data: impl Bits) {
+        let _ = self.try_write(data);
+    }
+
+    /// Try to write a frame and restart the deadline.
+    ///
+    /// After the deadline passed, the outputs are turned back on
+    /// when the fallback turned them off.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    pub fn try_write(&mut self, data: impl Bits) -> Result<(), Error> {
+        self.controller.try_write(&data)?;
+        self.try_feed()
+    }
+
+    /// Restart the deadline without writing a frame.
+    ///
+    /// Connector errors are ignored, see `try_feed/0`.
+    pub fn feed(&mut self) {
+        let _ = self.try_feed();
+    }
+
+    /// Try to restart the deadline without writing a frame,
+    /// for example for frames written by another producer.
+    ///
+    /// After the deadline passed, the outputs are turned back on
+    /// when the fallback turned them off.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to turn the outputs on.
+    pub fn try_feed(&mut self) -> Result<(), Error> {
+        self.last = self.time.now();
+
+        if self.expired {
+            if self.resume {
+                self.controller.try_on()?;
+            }
+
+            self.expired = false;
+            self.report(WatchdogEvent::Recovered);
+        }
+
+        Ok(())
+    }
+
+    /// Apply the fallback when the deadline passed.
+    ///
+    /// Connector errors are ignored, see `try_check/0`.
+    pub fn check(&mut self) -> bool {
+        self.try_check().unwrap_or(false)
+    }
+
+    /// Try to apply the fallback when the deadline passed.
+    ///
+    /// Returns whether the fallback was applied by this check.
+    /// The hook receives `WatchdogEvent::Expired` once the fallback is applied.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to apply the fallback,
+    /// it is retried on the next check.
+    pub fn try_check(&mut self) -> Result<bool, Error> {
+        let silence = self.time.now().saturating_sub(self.last);
+
+        if self.expired || silence < self.deadline {
+            return Ok(false);
+        }
+
+        let on = self.controller.is_on();
+        self.controller.try_fallback(&self.fallback)?;
+
+        self.resume = on && self.fallback == Fallback::Off;
+        self.expired = true;
+        self.report(WatchdogEvent::Expired { silence });
+
+        Ok(true)
+    }
+
+    /// Stop supervising and return the controller.
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.controller
+    }
+
+    /// Report an event to the hook.
+    fn report(&mut self, event: WatchdogEvent) {
+        if let Some(hook) = &mut self.hook {
+            hook(event);
+        }
+    }
+}
+
+impl<S: Supervised + Send + 'static, T: TimeSource + Send + 'static> Watchdog<S, T> {
+    /// Check the deadline every `period` on a dedicated thread.
+    ///
+    /// Frames are written through the returned `WatchdogThread`,
+    /// which can be shared with other threads.
+    #[must_use]
+    pub fn start(self, period: Duration) -> WatchdogThread<S, T> {
+        let watchdog = Arc::new(Mutex::new(self));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let watchdog = Arc::clone(&watchdog);
+            let stop = Arc::clone(&stop);
+
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    lock(&watchdog).check();
+                    std::thread::sleep(period);
+                }
+            })
+        };
+
+        WatchdogThread {
+            watchdog,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl<S: Supervised + core::fmt::Debug, T: TimeSource + core::fmt::Debug> core::fmt::Debug
+    for Watchdog<S, T>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("controller", &self.controller)
+            .field("time", &self.time)
+            .field("deadline", &self.deadline)
+            .field("fallback", &self.fallback)
+            .field("hook", &self.hook.is_some())
+            .field("last", &self.last)
+            .field("expired", &self.expired)
+            .field("resume", &self.resume)
+            .finish()
+    }
+}
+
+/// `Watchdog` checking its deadline on a dedicated thread.
+///
+/// Stops checking when dropped, see `stop/0` to get the watchdog back.
+#[derive(Debug)]
+pub struct WatchdogThread<
+    S: Supervised + Send + 'static,
+    T: TimeSource + Send + 'static = Monotonic,
+> {
+    /// Watchdog shared with the checking thread.
+    watchdog: Arc<Mutex<Watchdog<S, T>>>,
+
+    /// Stop signal for the checking thread.
+    stop: Arc<AtomicBool>,
+
+    /// Checking thread.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<S: Supervised + Send + 'static, T: TimeSource + Send + 'static> WatchdogThread<S, T> {
+    /// Write a frame and restart the deadline.
+    ///
+    /// Connector errors are ignored, see `try_write/1`.
+    pub fn write(&self, data: impl Bits) {
+        lock(&self.watchdog).write(data);
+    }
+
+    /// Try to write a frame and restart the deadline, see `Watchdog::try_write`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to set a pin.
+    pub fn try_write(&self, data: impl Bits) -> Result<(), Error> {
+        lock(&self.watchdog).try_write(data)
+    }
+
+    /// Restart the deadline without writing a frame.
+    ///
+    /// Connector errors are ignored, see `try_feed/0`.
+    pub fn feed(&self) {
+        lock(&self.watchdog).feed();
+    }
+
+    /// Try to restart the deadline without writing a frame, see `Watchdog::try_feed`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the connector fails to turn the outputs on.
+    pub fn try_feed(&self) -> Result<(), Error> {
+        lock(&self.watchdog).try_feed()
+    }
+
+    /// Whether the deadline passed and no frame was written since.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        lock(&self.watchdog).is_expired()
+    }
+
+    /// Stop checking and return the watchdog.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the checking thread panicked.
+    #[must_use]
+    pub fn stop(mut self) -> Watchdog<S, T> {
+        self.join();
+
+        let watchdog = Arc::clone(&self.watchdog);
+        drop(self);
+
+        Arc::into_inner(watchdog)
+            .expect("watchdog thread stopped")
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Signal the checking thread to stop and wait for it.
+    fn join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("watchdog thread not to panic");
+        }
+    }
+}
+
+impl<S: Supervised + Send + 'static, T: TimeSource + Send + 'static> Drop for WatchdogThread<S, T> {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+/// Lock a mutex, ignoring poisoning.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(all(test, feature = "emulator"))]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+
+    use super::*;
+    use crate::Emulator;
+
+    /// Mocked clock, advanced by hand.
+    #[derive(Debug, Clone, Default)]
+    struct Mock(Arc<AtomicU64>);
+
+    impl Mock {
+        /// Advance the clock.
+        fn advance(&self, by: Duration) {
+            let nanos = u64::try_from(by.as_nanos()).expect("short duration");
+            self.0.fetch_add(nanos, Ordering::Relaxed);
+        }
+    }
+
+    impl TimeSource for Mock {
+        fn now(&self) -> Duration {
+            Duration::from_nanos(self.0.load(Ordering::Relaxed))
+        }
+    }
+
+    /// Watchdog on an emulated chain with a 100 ms deadline.
+    fn watchdog(chain: usize, clock: &Mock) -> Watchdog<Controller<Emulator>, Mock> {
+        let mut controller = Controller::connect(Emulator::new(chain), chain);
+        controller.on();
+
+        Watchdog::with_time_source(controller, Duration::from_millis(100), clock.clone())
+    }
+
+    #[test]
+    fn turns_off() {
+        let clock = Mock::default();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut watchdog = watchdog(1, &clock).on_event({
+            let events = Arc::clone(&events);
+            move |event| events.lock().expect("events").push(event)
+        });
+
+        watchdog.write(0xFF_u8);
+        clock.advance(Duration::from_millis(99));
+        assert!(!watchdog.check());
+        assert!(watchdog.controller().connector().is_on());
+
+        clock.advance(Duration::from_millis(1));
+        assert!(watchdog.check());
+        assert!(watchdog.is_expired());
+        assert!(!watchdog.controller().connector().is_on());
+        assert_eq!(watchdog.controller().connector().register(0).state(), 0);
+
+        // Applied once.
+        clock.advance(Duration::from_secs(1));
+        assert!(!watchdog.check());
+
+        watchdog.write(0x0F_u8);
+        assert!(!watchdog.is_expired());
+        assert!(watchdog.controller().connector().is_on());
+        assert_eq!(watchdog.controller().connector().register(0).state(), 0xF0);
+
+        assert_eq!(
+            *events.lock().expect("events"),
+            [
+                WatchdogEvent::Expired {
+                    silence: Duration::from_millis(100)
+                },
+                WatchdogEvent::Recovered
+            ]
+        );
+    }
+
+    #[test]
+    fn safe_frame() {
+        let clock = Mock::default();
+        let mut watchdog = watchdog(2, &clock).with_fallback(Fallback::Frame(vec![0x01, 0x00]));
+
+        watchdog.write(0xFFFF_u16);
+        clock.advance(Duration::from_millis(50));
+        watchdog.feed();
+        clock.advance(Duration::from_millis(50));
+        assert!(!watchdog.check());
+
+        clock.advance(Duration::from_millis(50));
+        assert!(watchdog.check());
+
+        let emulator = watchdog.into_inner();
+        assert!(emulator.connector().is_on());
+        assert_eq!(emulator.connector().register(0).state(), 0);
+        assert_eq!(emulator.connector().register(1).state(), 0b1000_0000);
+    }
+
+    #[test]
+    fn thread() {
+        let clock = Mock::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let watchdog = watchdog(1, &clock)
+            .on_event(move |event| {
+                let _ = sender.send(event);
+            })
+            .start(Duration::from_millis(1));
+
+        watchdog.write(0xAA_u8);
+        clock.advance(Duration::from_millis(150));
+
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Ok(WatchdogEvent::Expired {
+                silence: Duration::from_millis(150)
+            })
+        );
+        assert!(watchdog.is_expired());
+
+        watchdog.write(0xAA_u8);
+        assert_eq!(receiver.recv(), Ok(WatchdogEvent::Recovered));
+
+        let watchdog = watchdog.stop();
+        assert!(!watchdog.is_expired());
+        assert!(watchdog.controller().connector().is_on());
+    }
+
+    #[test]
+    fn handle() {
+        let clock = Mock::default();
+        let mut controller = Controller::connect(Emulator::new(1), 1);
+        controller.on();
+        let thread = crate::ControllerThread::start(controller);
+        let mut watchdog =
+            Watchdog::with_time_source(thread.handle(), Duration::from_millis(100), clock.clone());
+
+        watchdog.write(0xFF_u8);
+        clock.advance(Duration::from_millis(100));
+        assert!(watchdog.check());
+        assert!(!watchdog.controller().is_on());
+
+        // Frames written by another producer.
+        thread.handle().write(0x0F_u8).expect("write");
+        watchdog.feed();
+        assert!(!watchdog.is_expired());
+
+        let controller = thread.stop();
+        assert!(controller.connector().is_on());
+        assert_eq!(controller.connector().register(0).state(), 0xF0);
+    }
+}